@@ -0,0 +1,184 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Index, Type};
+
+/// Derives `multiverse_ecs::class::Class` for a struct, exposing each of its fields as a component.
+/// Fields marked with `#[class(skip)]` are not exposed as components.
+///
+/// Components are looked up by type, so no two exposed fields may have the same type.
+/// This is checked at compile time for structs without generic parameters.
+///
+/// `Class::clone_box` is implemented for structs which implement `Clone`, but cannot detect `Clone` on structs with generic parameters,
+/// so it always returns None for them unless the struct is marked `#[class(clone)]`.
+/// That attribute implements `Class` only when the struct implements `Clone`, and clones it directly.
+#[proc_macro_derive(Class, attributes(class))]
+pub fn derive_class(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct Component {
+    name: String,
+    member: TokenStream2,
+    ty: Type,
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Class can only be derived for structs",
+            ))
+        }
+    };
+    let components = components(fields)?;
+    let clone = clone(&input)?;
+
+    // Component types must be 'static to be looked up by TypeId, and meet the thread-safety bounds of classes
+    let where_clause = input.generics.make_where_clause();
+    for component in &components {
+        let ty = &component.ty;
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: 'static + Send + Sync));
+    }
+    if clone {
+        where_clause.predicates.push(parse_quote!(Self: Clone));
+    }
+
+    let name = &input.ident;
+    let name_str = name.to_string();
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let names = components.iter().map(|component| &component.name);
+    let members = components
+        .iter()
+        .map(|component| &component.member)
+        .collect::<Vec<_>>();
+    let types = components
+        .iter()
+        .map(|component| &component.ty)
+        .collect::<Vec<_>>();
+
+    // Fails to compile if the same component type appears more than once in a class
+    let check_distinct = if input.generics.params.is_empty() {
+        quote! {
+            const _: () = {
+                #[allow(dead_code)]
+                trait ComponentTypesMustBeDistinct {}
+                #(impl ComponentTypesMustBeDistinct for #types {})*
+            };
+        }
+    } else {
+        TokenStream2::new()
+    };
+
+    // Autoref specialization cannot see through generic parameters, so structs marked `#[class(clone)]` are cloned directly instead
+    let clone_box = if clone {
+        quote! {
+            Some(Box::new(::std::clone::Clone::clone(self)))
+        }
+    } else {
+        quote! {
+            #[allow(unused_imports)]
+            use ::multiverse_ecs::class::{__CloneClassFallback, __CloneClassViaClone};
+            (&::multiverse_ecs::class::__CloneClass(self)).__clone_box()
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::multiverse_ecs::class::Class for #name #type_generics #where_clause {
+            fn name(&self) -> &'static str {
+                #name_str
+            }
+
+            fn component(&self, type_id: ::std::any::TypeId) -> Option<&dyn ::std::any::Any> {
+                #(
+                    if type_id == ::std::any::TypeId::of::<#types>() {
+                        return Some(&self.#members as &dyn ::std::any::Any);
+                    }
+                )*
+                None
+            }
+
+            fn component_mut(&mut self, type_id: ::std::any::TypeId) -> Option<&mut dyn ::std::any::Any> {
+                #(
+                    if type_id == ::std::any::TypeId::of::<#types>() {
+                        return Some(&mut self.#members as &mut dyn ::std::any::Any);
+                    }
+                )*
+                None
+            }
+
+            fn components_iter(&self) -> ::multiverse_ecs::class::ComponentsIter<'_> {
+                Box::new(vec![#((#names, ::std::any::TypeId::of::<#types>(), &self.#members as &dyn ::std::any::Any)),*].into_iter())
+            }
+
+            fn component_type_ids(&self) -> Vec<::std::any::TypeId> {
+                vec![#(::std::any::TypeId::of::<#types>()),*]
+            }
+
+            fn clone_box(&self) -> Option<Box<dyn ::multiverse_ecs::class::Class>> {
+                #clone_box
+            }
+        }
+
+        #check_distinct
+    })
+}
+
+// Returns whether a struct is marked `#[class(clone)]`
+fn clone(input: &DeriveInput) -> syn::Result<bool> {
+    let mut clone = false;
+    for attr in &input.attrs {
+        if attr.path().is_ident("class") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("clone") {
+                    clone = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported class attribute"))
+                }
+            })?;
+        }
+    }
+    Ok(clone)
+}
+
+// Collects the fields of a struct which are exposed as components
+fn components(fields: &Fields) -> syn::Result<Vec<Component>> {
+    let mut components = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let mut skip = false;
+        for attr in &field.attrs {
+            if attr.path().is_ident("class") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("skip") {
+                        skip = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("unsupported class attribute"))
+                    }
+                })?;
+            }
+        }
+        if skip {
+            continue;
+        }
+
+        let (name, member) = match &field.ident {
+            Some(ident) => (ident.to_string(), ident.to_token_stream()),
+            None => (index.to_string(), Index::from(index).to_token_stream()),
+        };
+        components.push(Component {
+            name,
+            member,
+            ty: field.ty.clone(),
+        });
+    }
+    Ok(components)
+}
@@ -2,50 +2,230 @@ use std::any::{Any, TypeId};
 
 use as_any::AsAny;
 
-pub trait Class: AsAny {
+/// An iterator over the components of a class, yielding each component's field name, type id, and value.
+pub type ComponentsIter<'a> = Box<dyn Iterator<Item = (&'static str, TypeId, &'a dyn Any)> + 'a>;
+
+/// A set of components which nodes are created with, such as those defined by `define_class!`.
+/// Classes must be `Send + Sync` so that nodes can be shared with other threads, such as by the parallel iterators of the `rayon` feature.
+pub trait Class: AsAny + Send + Sync {
     fn name(&self) -> &'static str;
     fn component(&self, type_id: TypeId) -> Option<&dyn Any>;
     fn component_mut(&mut self, type_id: TypeId) -> Option<&mut dyn Any>;
+    /// Returns an iterator over the components of this class.
+    /// `define_class!` implements this automatically, yielding each of the class's fields.
+    fn components_iter(&self) -> ComponentsIter<'_> {
+        Box::new(std::iter::empty())
+    }
+    /// Returns the type ids of the components of this class.
+    /// By default these are collected from `components_iter`; `define_class!` implements this directly.
+    fn component_type_ids(&self) -> Vec<TypeId> {
+        self.components_iter()
+            .map(|(_name, type_id, _component)| type_id)
+            .collect()
+    }
+    /// Returns a boxed copy of this class, or None if the class cannot be cloned.
+    /// `define_class!` implements this automatically for classes which implement `Clone`, except for classes with generic parameters unless they are marked `#[class(clone)]`.
+    fn clone_box(&self) -> Option<Box<dyn Class>> {
+        None
+    }
 }
 
+/// Defines one or more classes, each of which exposes its fields as components.
+/// Classes can be defined with named fields (`class Foo { a: A, b: B }`)
+/// or as a tuple of components (`class Foo(A, B);`).
+///
+/// Fields of a class with named fields can be given a default value (`class Foo { a: A = A::new(), b: B }`).
+/// If any field has one, `Default` is implemented for the class, using `Default::default()` for the other fields.
+///
+/// Classes can have generic type parameters, such as `class Wrapper<T> { value: T }`.
+/// The `'static`, `Send` and `Sync` bounds needed on their component types are added automatically.
+///
+/// `Class::clone_box` is implemented for classes which implement `Clone`, but cannot detect `Clone` on classes with generic parameters,
+/// so it always returns None for them unless `#[class(clone)]` is given as the class's first attribute.
+/// That attribute implements `Class` only when the class implements `Clone`, and clones it directly:
+/// ```
+/// # use multiverse_ecs::{class::Class, define_class};
+/// define_class! {
+///     #[class(clone)]
+///     #[derive(Clone)]
+///     class Wrapper<T> {
+///         value: T,
+///     }
+/// }
+/// assert!(Wrapper { value: 5u32 }.clone_box().is_some());
+/// ```
+///
+/// Components are looked up by type, so no two fields of a class may have the same type.
+/// This is checked at compile time for classes without generic parameters:
+/// ```compile_fail,E0119
+/// # use multiverse_ecs::define_class;
+/// define_class! {
+///     class Player {
+///         health: u32,
+///         score: u32,
+///     }
+/// }
+/// ```
 #[macro_export]
 macro_rules! define_class {
-    ($(
+    (@impl [$($clone:path)?] $name:ident [$($impl_generics:tt)*] [$($type_generics:tt)*] [$($field:tt: $type:ty),*]) => {
+        // Component types must be 'static to be looked up by TypeId, and meet the thread-safety bounds of classes
+        impl$($impl_generics)* $crate::class::Class for $name$($type_generics)* where $($type: 'static + Send + Sync,)* $(Self: $clone)? {
+            fn name(&self) -> &'static str {
+                stringify!($name)
+            }
+
+            fn component(&self, type_id: std::any::TypeId) -> Option<&dyn std::any::Any> {
+                $crate::define_class!(@lookup [$($impl_generics)*] type_id [$(&self.$field as &dyn std::any::Any => $type),*])
+            }
+
+            fn component_mut(&mut self, type_id: std::any::TypeId) -> Option<&mut dyn std::any::Any> {
+                $crate::define_class!(@lookup [$($impl_generics)*] type_id [$(&mut self.$field as &mut dyn std::any::Any => $type),*])
+            }
+
+            fn components_iter(&self) -> $crate::class::ComponentsIter<'_> {
+                Box::new(vec![$((stringify!($field), std::any::TypeId::of::<$type>(), &self.$field as &dyn std::any::Any)),*].into_iter())
+            }
+
+            fn component_type_ids(&self) -> Vec<std::any::TypeId> {
+                vec![$(std::any::TypeId::of::<$type>()),*]
+            }
+
+            fn clone_box(&self) -> Option<Box<dyn $crate::class::Class>> {
+                $crate::define_class!(@clone_box self [$($clone)?])
+            }
+        }
+    };
+
+    // Clones a class through `Clone` if it was marked with `#[class(clone)]`, and otherwise only if it implements `Clone`.
+    // Autoref specialization cannot see through generic parameters, so unmarked classes with generics are never cloned.
+    (@clone_box $self:ident []) => {{
+        #[allow(unused_imports)]
+        use $crate::class::{__CloneClassFallback, __CloneClassViaClone};
+        (&$crate::class::__CloneClass($self)).__clone_box()
+    }};
+    (@clone_box $self:ident [$clone:path]) => {
+        Some(Box::new(std::clone::Clone::clone($self)))
+    };
+
+    // Returns the component whose type has the given type id.
+    // Const patterns cannot depend on generic parameters, so classes with generics compare the type ids one at a time instead.
+    (@lookup [] $type_id:ident [$($component:expr => $type:ty),*]) => {{
+        #[allow(unreachable_patterns)]
+        match $type_id {
+            $(const { std::any::TypeId::of::<$type>() } => Some($component),)*
+            _ => None,
+        }
+    }};
+    (@lookup [$($generics:tt)+] $type_id:ident [$($component:expr => $type:ty),*]) => {
+        $(if $type_id == std::any::TypeId::of::<$type>() {
+            Some($component)
+        } else)* {
+            None
+        }
+    };
+
+    // Implements `Default` for a class if any of its fields were given a default value
+    (@default $name:ident [$($impl_generics:tt)*] [$($type_generics:tt)*] [$($field:ident $(= $default:expr)?),*] []) => {};
+    (@default $name:ident [$($impl_generics:tt)*] [$($type_generics:tt)*] [$($field:ident $(= $default:expr)?),*] [$($defaults:tt)+]) => {
+        impl$($impl_generics)* std::default::Default for $name$($type_generics)* {
+            fn default() -> Self {
+                Self {
+                    $($field: $crate::define_class!(@default_value $($default)?)),*
+                }
+            }
+        }
+    };
+    (@default_value $default:expr) => {
+        $default
+    };
+    (@default_value) => {
+        std::default::Default::default()
+    };
+
+    // Fails to compile if the same component type appears more than once in a class
+    (@check_distinct [] [$($type:ty),*]) => {
+        const _: () = {
+            #[allow(dead_code)]
+            trait ComponentTypesMustBeDistinct {}
+            $(impl ComponentTypesMustBeDistinct for $type {})*
+        };
+    };
+    (@check_distinct [$($generics:tt)+] [$($type:ty),*]) => {};
+
+    // Pairs each field of a tuple class with its index
+    (@tuple [$($clone:path)?] $name:ident [$($field:tt: $type:ty,)*] [$index:tt $($indices:tt)*] $next_type:ty, $($rest:ty,)*) => {
+        $crate::define_class!(@tuple [$($clone)?] $name [$($field: $type,)* $index: $next_type,] [$($indices)*] $($rest,)*);
+    };
+    (@tuple [$($clone:path)?] $name:ident [$($field:tt: $type:ty,)*] [$($indices:tt)*]) => {
+        $crate::define_class!(@impl [$($clone)?] $name [] [] [$($field: $type),*]);
+    };
+
+    (
+        @class [$($clone:path)?]
         $(#[$outer:meta])*
         $pub:vis class $name:ident$(<$($lifetime:lifetime,)*$($generic:ident$(:$bound:tt$(+$add_bound:tt)*)?),*>)? {
-            $($(#[$field_outer:meta])*$field:ident: $type:ty),*
+            $($(#[$field_outer:meta])*$field:ident: $type:ty $(= $default:expr)?),*
             $(,)?
         }
-    )*) => {
-        $(
-            $(#[$outer])*
-            $pub struct $name$(<$($lifetime,)*$($generic$(:$bound$(+$add_bound)*)?),*>)? {
-                $($(#[$field_outer])*$field: $type),*
-            }
+        $($rest:tt)*
+    ) => {
+        $(#[$outer])*
+        $pub struct $name$(<$($lifetime,)*$($generic$(:$bound$(+$add_bound)*)?),*>)? {
+            $($(#[$field_outer])*$field: $type),*
+        }
 
-            impl$(<$($lifetime,)*$($generic$(:$bound$(+$add_bound)*)?),*>)? $crate::class::Class for $name$(<$($lifetime,)*$($generic),*>)? {
-                fn name(&self) -> &'static str {
-                    stringify!($name)
-                }
+        $crate::define_class!(
+            @impl [$($clone)?] $name
+            [$(<$($lifetime,)*$($generic$(:$bound$(+$add_bound)*)?),*>)?]
+            [$(<$($lifetime,)*$($generic),*>)?]
+            [$($field: $type),*]
+        );
 
-                fn component(&self, type_id: std::any::TypeId) -> Option<&dyn std::any::Any> {
-                    #[allow(unreachable_patterns)]
-                    match type_id {
-                        $(const { std::any::TypeId::of::<$type>() } => Some(&self.$field as &dyn std::any::Any),)*
-                        _ => None,
-                    }
-                }
+        $crate::define_class!(
+            @default $name
+            [$(<$($lifetime,)*$($generic$(:$bound$(+$add_bound)*)?),*>)?]
+            [$(<$($lifetime,)*$($generic),*>)?]
+            [$($field $(= $default)?),*]
+            [$($($default)?)*]
+        );
 
-                fn component_mut(&mut self, type_id: std::any::TypeId) -> Option<&mut dyn std::any::Any> {
-                    #[allow(unreachable_patterns)]
-                    match type_id {
-                        $(const { std::any::TypeId::of::<$type>() } => Some(&mut self.$field as &mut dyn std::any::Any),)*
-                        _ => None,
-                    }
-                }
-            }
-        )*
+        $crate::define_class!(
+            @check_distinct
+            [$(<$($lifetime,)*$($generic),*>)?]
+            [$($type),*]
+        );
+
+        $crate::define_class!($($rest)*);
+    };
+
+    (
+        @class [$($clone:path)?]
+        $(#[$outer:meta])*
+        $pub:vis class $name:ident(
+            $($(#[$field_outer:meta])*$type:ty),*
+            $(,)?
+        );
+        $($rest:tt)*
+    ) => {
+        $(#[$outer])*
+        $pub struct $name($($(#[$field_outer])*$type),*);
+
+        $crate::define_class!(@tuple [$($clone)?] $name [] [0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15] $($type,)*);
+
+        $crate::define_class!(@check_distinct [] [$($type),*]);
+
+        $crate::define_class!($($rest)*);
+    };
+
+    (#[class(clone)] $($rest:tt)+) => {
+        $crate::define_class!(@class [std::clone::Clone] $($rest)+);
+    };
+    ($(#[$outer:meta])* $pub:vis class $($rest:tt)+) => {
+        $crate::define_class!(@class [] $(#[$outer])* $pub class $($rest)+);
     };
+
+    () => {};
 }
 
 impl Class for () {
@@ -60,6 +240,37 @@ impl Class for () {
     fn component_mut(&mut self, _type_id: TypeId) -> Option<&mut dyn Any> {
         None
     }
+
+    fn clone_box(&self) -> Option<Box<dyn Class>> {
+        Some(Box::new(()))
+    }
+}
+
+// Used by `define_class!` to implement `Class::clone_box` only for classes which implement `Clone`.
+// Method resolution prefers `__CloneClassViaClone` when it applies, and falls back to `__CloneClassFallback` otherwise.
+#[doc(hidden)]
+pub struct __CloneClass<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub trait __CloneClassViaClone {
+    fn __clone_box(&self) -> Option<Box<dyn Class>>;
+}
+
+impl<T: Class + Clone> __CloneClassViaClone for __CloneClass<'_, T> {
+    fn __clone_box(&self) -> Option<Box<dyn Class>> {
+        Some(Box::new(self.0.clone()))
+    }
+}
+
+#[doc(hidden)]
+pub trait __CloneClassFallback {
+    fn __clone_box(&self) -> Option<Box<dyn Class>>;
+}
+
+impl<T> __CloneClassFallback for &__CloneClass<'_, T> {
+    fn __clone_box(&self) -> Option<Box<dyn Class>> {
+        None
+    }
 }
 
 pub trait ClassDynComponent {
@@ -0,0 +1,46 @@
+/// An error returned by the fallible operations of a universe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The handle does not point to a node in the universe.
+    InvalidHandle,
+    /// The parent handle does not point to a node in the universe.
+    InvalidParent,
+    /// The new parent is the node itself or one of its descendants.
+    WouldCreateCycle,
+    /// The nodes do not share a parent, and are not both root nodes.
+    NotSiblings,
+    /// The class of a node cannot be cloned (see `Class::clone_box`).
+    UncloneableClass,
+    /// The node has dynamic components, which cannot be cloned.
+    UncloneableComponent,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidHandle => {
+                write!(f, "The handle does not point to a node")
+            }
+            Error::InvalidParent => {
+                write!(f, "The parent handle does not point to a node")
+            }
+            Error::WouldCreateCycle => {
+                write!(
+                    f,
+                    "A node cannot become a child of itself or its descendants"
+                )
+            }
+            Error::NotSiblings => {
+                write!(f, "The nodes do not share a parent")
+            }
+            Error::UncloneableClass => {
+                write!(f, "The class of a node cannot be cloned")
+            }
+            Error::UncloneableComponent => {
+                write!(f, "The dynamic components of a node cannot be cloned")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+
+use ggutil::prelude::*;
+use petgraph::graph::{Graph, NodeIndex};
+
+use crate::universe::Universe;
+
+impl Universe {
+    /// Converts the node hierarchy of the universe into a petgraph graph, with edges running from parent to child.
+    /// Each graph node is weighted with the handle of the universe node it represents.
+    /// Returns the graph along with the index of each universe node in the graph.
+    pub fn to_petgraph(&self) -> (Graph<Handle, ()>, HashMap<Handle, NodeIndex>) {
+        let mut graph = Graph::new();
+        let indices = self
+            .nodes()
+            .map(|node| (node.handle().clone(), graph.add_node(node.handle().clone())))
+            .collect::<HashMap<_, _>>();
+        for node in self.nodes() {
+            for child_handle in node.children() {
+                graph.add_edge(indices[node.handle()], indices[child_handle], ());
+            }
+        }
+        (graph, indices)
+    }
+}
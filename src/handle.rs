@@ -0,0 +1,102 @@
+use std::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::Deref,
+};
+
+use crate::class::Class;
+
+/// A handle to a node in a universe.
+/// This is ggutil's `Handle`, re-exported so that it can be named without depending on ggutil directly:
+/// ```
+/// use multiverse_ecs::{handle::Handle, universe::Universe};
+///
+/// fn first_root(universe: &Universe) -> Option<&Handle> {
+///     universe.root_node_handles().first()
+/// }
+///
+/// let mut universe = Universe::new();
+/// let handle: Handle = universe.create_node(None, ());
+/// assert_eq!(first_root(&universe), Some(&handle));
+/// ```
+pub use ggutil::prelude::Handle;
+
+/// A handle to a node which is known to have a class of type C.
+/// Dereferences to the untyped `Handle`, so it can be used anywhere a handle is expected.
+///
+/// Typed handles can only be used to look up nodes of the class they were created with:
+/// ```compile_fail,E0308
+/// # use multiverse_ecs::{define_class, universe::Universe};
+/// define_class! {
+///     class Cat {
+///         lives: u8,
+///     }
+///
+///     class Dog {
+///         age: u32,
+///     }
+/// }
+///
+/// let mut universe = Universe::new();
+/// let dog_handle = universe.create_node_typed(None, Dog { age: 3 });
+/// let cat: Option<&Cat> = universe.node_typed(&dog_handle);
+/// ```
+pub struct TypedHandle<C: Class> {
+    handle: Handle,
+    __marker: PhantomData<fn() -> C>,
+}
+
+impl<C: Class> TypedHandle<C> {
+    pub(crate) fn __new(handle: Handle) -> Self {
+        Self {
+            handle,
+            __marker: PhantomData,
+        }
+    }
+
+    /// Returns the untyped handle.
+    pub fn into_handle(self) -> Handle {
+        self.handle
+    }
+}
+
+impl<C: Class> Deref for TypedHandle<C> {
+    type Target = Handle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+impl<C: Class> From<TypedHandle<C>> for Handle {
+    fn from(typed_handle: TypedHandle<C>) -> Self {
+        typed_handle.handle
+    }
+}
+
+impl<C: Class> Clone for TypedHandle<C> {
+    fn clone(&self) -> Self {
+        Self::__new(self.handle.clone())
+    }
+}
+
+impl<C: Class> PartialEq for TypedHandle<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl<C: Class> Eq for TypedHandle<C> {}
+
+impl<C: Class> Hash for TypedHandle<C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state);
+    }
+}
+
+impl<C: Class> Debug for TypedHandle<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TypedHandle").field(&self.handle).finish()
+    }
+}
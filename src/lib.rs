@@ -2,15 +2,30 @@
 #![feature(inline_const_pat)]
 #![feature(const_type_id)]
 
+// Lets code generated by `#[derive(Class)]` refer to this crate by name from within it
+extern crate self as multiverse_ecs;
+
 pub mod class;
+pub mod error;
+#[cfg(feature = "petgraph")]
+mod graph;
+pub mod handle;
 pub mod node;
+pub mod prelude;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod system;
 pub mod universe;
 
+#[cfg(feature = "derive")]
+pub use multiverse_ecs_derive::Class;
+
 #[cfg(test)]
 mod tests {
     use crate::{
         define_class,
-        universe::{NodesIter, Universe},
+        error::Error,
+        universe::{NodesIter, NodesIterMut, Universe, UniverseDiff, Walk},
     };
 
     #[test]
@@ -46,8 +61,13 @@ mod tests {
             &[node_handle3.clone(), node_handle4]
         );
 
+        // Detach node 3 from node 2 so it can become node 2's parent
+        universe.change_parent(&node_handle3, None).unwrap();
+
         // Change node 2's parent to node 3
-        universe.change_parent(&node_handle2, Some(&node_handle3));
+        universe
+            .change_parent(&node_handle2, Some(&node_handle3))
+            .unwrap();
 
         // Assert that node 2 has node 3 as its parent
         assert_eq!(
@@ -170,4 +190,4216 @@ mod tests {
             &[&dog_node_handle2]
         );
     }
+
+    #[test]
+    fn node_removal() {
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create node 1
+        let node_handle1 = universe.create_node(None, ());
+
+        // Create node 2 as a child node for node 1
+        let node_handle2 = universe.create_node(Some(&node_handle1), ());
+
+        // Create 2 children nodes for node 2
+        let node_handle3 = universe.create_node(Some(&node_handle2), ());
+        let node_handle4 = universe.create_node(Some(&node_handle2), ());
+
+        // Remove node 2
+        assert_eq!(universe.remove_node(&node_handle2), Ok(()));
+
+        // Assert that node 2 no longer exists
+        assert!(universe.node(&node_handle2).is_none());
+
+        // Assert that removing node 2 again fails
+        assert_eq!(
+            universe.remove_node(&node_handle2),
+            Err(Error::InvalidHandle)
+        );
+
+        // Assert that node 3 and node 4 now have node 1 as their parent
+        assert_eq!(
+            universe.node(&node_handle3).unwrap().parent(),
+            Some(&node_handle1)
+        );
+        assert_eq!(
+            universe.node(&node_handle4).unwrap().parent(),
+            Some(&node_handle1)
+        );
+
+        // Assert that node 1 has node 3 and node 4 as its only children
+        assert_eq!(
+            universe.node(&node_handle1).unwrap().children(),
+            &[node_handle3.clone(), node_handle4.clone()]
+        );
+
+        // Remove node 1
+        universe.remove_node(&node_handle1).unwrap();
+
+        // Assert that node 3 and node 4 are now root nodes
+        assert_eq!(universe.node(&node_handle3).unwrap().parent(), None);
+        assert_eq!(universe.root_node_handles(), &[node_handle3, node_handle4]);
+    }
+
+    #[test]
+    fn subtree_removal() {
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a 3-level tree
+        let node_handle1 = universe.create_node(None, ());
+        let node_handle2 = universe.create_node(Some(&node_handle1), ());
+        let node_handle3 = universe.create_node(Some(&node_handle1), ());
+        let node_handle4 = universe.create_node(Some(&node_handle2), ());
+        let node_handle5 = universe.create_node(Some(&node_handle2), ());
+        let node_handle6 = universe.create_node(Some(&node_handle3), ());
+
+        // Remove the subtree beginning at node 2
+        assert_eq!(universe.remove_subtree(&node_handle2), 3);
+
+        // Assert that node 2 and its children no longer exist
+        assert!(universe.node(&node_handle2).is_none());
+        assert!(universe.node(&node_handle4).is_none());
+        assert!(universe.node(&node_handle5).is_none());
+
+        // Assert that the rest of the tree is intact
+        assert_eq!(
+            universe.node(&node_handle1).unwrap().children(),
+            &[node_handle3.clone()]
+        );
+        assert_eq!(
+            universe.node(&node_handle3).unwrap().children(),
+            &[node_handle6]
+        );
+        assert_eq!(universe.root_node_handles(), &[node_handle1]);
+
+        // Assert that removing the subtree again removes nothing
+        assert_eq!(universe.remove_subtree(&node_handle2), 0);
+    }
+
+    #[test]
+    fn reparent_cycle_detection() {
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a parent node with a child node
+        let parent_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(Some(&parent_handle), ());
+
+        // Assert that making the parent a child of its own child is rejected
+        assert_eq!(
+            universe.change_parent(&parent_handle, Some(&child_handle)),
+            Err(Error::WouldCreateCycle)
+        );
+
+        // Assert that making the parent a child of itself is rejected
+        assert_eq!(
+            universe.change_parent(&parent_handle, Some(&parent_handle)),
+            Err(Error::WouldCreateCycle)
+        );
+
+        // Assert that both nodes are unchanged
+        assert_eq!(universe.node(&parent_handle).unwrap().parent(), None);
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().children(),
+            &[child_handle.clone()]
+        );
+        assert_eq!(
+            universe.node(&child_handle).unwrap().parent(),
+            Some(&parent_handle)
+        );
+        assert_eq!(universe.node(&child_handle).unwrap().children(), &[]);
+    }
+
+    #[test]
+    fn node_ancestors() {
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a 4-deep chain of nodes
+        let node_handle1 = universe.create_node(None, ());
+        let node_handle2 = universe.create_node(Some(&node_handle1), ());
+        let node_handle3 = universe.create_node(Some(&node_handle2), ());
+        let node_handle4 = universe.create_node(Some(&node_handle3), ());
+
+        // Assert that the ancestors of node 4 are returned from child to root
+        assert_eq!(
+            universe
+                .ancestors(&node_handle4)
+                .handles()
+                .collect::<Vec<_>>(),
+            &[&node_handle3, &node_handle2, &node_handle1]
+        );
+
+        // Assert that the root node has no ancestors
+        assert_eq!(universe.ancestors(&node_handle1).count(), 0);
+    }
+
+    #[test]
+    fn node_descendants() {
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a small tree
+        let node_handle1 = universe.create_node(None, ());
+        let node_handle2 = universe.create_node(Some(&node_handle1), ());
+        let node_handle3 = universe.create_node(Some(&node_handle2), ());
+        let node_handle4 = universe.create_node(Some(&node_handle2), ());
+        let node_handle5 = universe.create_node(Some(&node_handle1), ());
+        let node_handle6 = universe.create_node(Some(&node_handle5), ());
+
+        // Assert that the descendants of node 1 are returned in depth-first pre-order
+        assert_eq!(
+            universe
+                .descendants(&node_handle1)
+                .handles()
+                .collect::<Vec<_>>(),
+            &[
+                &node_handle2,
+                &node_handle3,
+                &node_handle4,
+                &node_handle5,
+                &node_handle6
+            ]
+        );
+
+        // Assert that a leaf node has no descendants
+        assert_eq!(universe.descendants(&node_handle6).count(), 0);
+    }
+
+    #[test]
+    fn node_descendants_bfs() {
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a small tree
+        let node_handle1 = universe.create_node(None, ());
+        let node_handle2 = universe.create_node(Some(&node_handle1), ());
+        let node_handle3 = universe.create_node(Some(&node_handle2), ());
+        let node_handle4 = universe.create_node(Some(&node_handle2), ());
+        let node_handle5 = universe.create_node(Some(&node_handle1), ());
+        let node_handle6 = universe.create_node(Some(&node_handle5), ());
+
+        // Assert that the descendants of node 1 are returned level by level
+        assert_eq!(
+            universe
+                .descendants_bfs(&node_handle1)
+                .handles()
+                .collect::<Vec<_>>(),
+            &[
+                &node_handle2,
+                &node_handle5,
+                &node_handle3,
+                &node_handle4,
+                &node_handle6
+            ]
+        );
+
+        // Assert that the breadth-first order differs from the depth-first order
+        assert_ne!(
+            universe
+                .descendants_bfs(&node_handle1)
+                .handles()
+                .collect::<Vec<_>>(),
+            universe
+                .descendants(&node_handle1)
+                .handles()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn node_multi_component_lookup() {
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Name(String);
+        #[derive(Debug, PartialEq)]
+        struct Age(u32);
+        #[derive(Debug, PartialEq)]
+        struct Weight(f32);
+
+        // Define some classes of components
+        define_class! {
+            class Cat {
+                name: Name,
+                age: Age,
+                weight: Weight,
+            }
+
+            class Rock {
+                weight: Weight,
+            }
+        }
+
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a cat node and a rock node
+        let cat_node_handle = universe.create_node(
+            None,
+            Cat {
+                name: Name("Garfield".to_string()),
+                age: Age(5),
+                weight: Weight(12.0),
+            },
+        );
+        universe.create_node(
+            None,
+            Rock {
+                weight: Weight(100.0),
+            },
+        );
+
+        // Test search the universe for nodes with both a Name and an Age component
+        assert_eq!(
+            universe
+                .nodes()
+                .with_components::<(Name, Age)>()
+                .map(|(node, name, age)| (node.handle(), name, age))
+                .collect::<Vec<_>>(),
+            &[(&cat_node_handle, &Name("Garfield".to_string()), &Age(5))]
+        );
+
+        // Test search the universe for nodes with both an Age and a Weight component
+        assert_eq!(
+            universe.nodes().with_components::<(Age, Weight)>().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn node_component_exclusion() {
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Position(f32);
+        #[derive(Debug, PartialEq)]
+        struct Velocity(f32);
+
+        // Define some classes of components
+        define_class! {
+            class Ball {
+                position: Position,
+                velocity: Velocity,
+            }
+
+            class Wall {
+                position: Position,
+            }
+        }
+
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a ball node and two wall nodes
+        universe.create_node(
+            None,
+            Ball {
+                position: Position(0.0),
+                velocity: Velocity(1.0),
+            },
+        );
+        let wall_node_handle1 = universe.create_node(
+            None,
+            Wall {
+                position: Position(-10.0),
+            },
+        );
+        let wall_node_handle2 = universe.create_node(
+            None,
+            Wall {
+                position: Position(10.0),
+            },
+        );
+
+        // Test search the universe for nodes without a Velocity component
+        assert_eq!(
+            universe
+                .nodes()
+                .without_component::<Velocity>()
+                .handles()
+                .collect::<Vec<_>>(),
+            &[&wall_node_handle1, &wall_node_handle2]
+        );
+
+        // Test search the universe for nodes without a Position component
+        assert_eq!(universe.nodes().without_component::<Position>().count(), 0);
+
+        // Move every node without a Velocity component
+        for node in universe.nodes_mut().without_component::<Velocity>() {
+            node.component_mut::<Position>().unwrap().0 += 1.0;
+        }
+
+        // Assert that only the walls were moved
+        assert_eq!(
+            universe
+                .nodes()
+                .with_component::<Position>()
+                .map(|(_node, position)| position)
+                .collect::<Vec<_>>(),
+            &[&Position(0.0), &Position(-9.0), &Position(11.0)]
+        );
+    }
+
+    #[test]
+    fn node_dynamic_component_insertion() {
+        // Define a component
+        #[derive(Debug, PartialEq)]
+        struct Health(u32);
+
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a node with no components
+        let node_handle = universe.create_node(None, ());
+
+        // Assert that the node has no Health component
+        assert_eq!(
+            universe.node(&node_handle).unwrap().component::<Health>(),
+            None
+        );
+
+        // Insert a Health component into the node
+        assert_eq!(universe.insert_component(&node_handle, Health(100)), None);
+
+        // Assert that the node now has the Health component
+        assert_eq!(
+            universe.node(&node_handle).unwrap().component::<Health>(),
+            Some(&Health(100))
+        );
+
+        // Mutate the Health component
+        universe
+            .node_mut(&node_handle)
+            .unwrap()
+            .component_mut::<Health>()
+            .unwrap()
+            .0 -= 10;
+
+        // Insert a Health component over the existing one and assert the old value is returned
+        assert_eq!(
+            universe.insert_component(&node_handle, Health(50)),
+            Some(Health(90))
+        );
+        assert_eq!(
+            universe.node(&node_handle).unwrap().component::<Health>(),
+            Some(&Health(50))
+        );
+    }
+
+    #[test]
+    fn node_dynamic_component_removal() {
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Name(String);
+        #[derive(Debug, PartialEq)]
+        struct Health(u32);
+
+        // Define a class of components
+        define_class! {
+            class Player {
+                name: Name,
+            }
+        }
+
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a player node with a dynamic Health component
+        let node_handle = universe.create_node(
+            None,
+            Player {
+                name: Name("Link".to_string()),
+            },
+        );
+        universe.insert_component(&node_handle, Health(3));
+
+        // Assert that the node has the Health component
+        let node = universe.node(&node_handle).unwrap();
+        assert_eq!(node.component::<Health>(), Some(&Health(3)));
+
+        // Remove the Health component and assert it is returned
+        assert_eq!(
+            universe.remove_component::<Health>(&node_handle),
+            Some(Health(3))
+        );
+
+        // Assert that the node no longer has the Health component
+        let node = universe.node(&node_handle).unwrap();
+        assert_eq!(node.component::<Health>(), None);
+        assert_eq!(universe.remove_component::<Health>(&node_handle), None);
+
+        // Assert that the class-defined Name component cannot be removed
+        assert_eq!(universe.remove_component::<Name>(&node_handle), None);
+        let node = universe.node(&node_handle).unwrap();
+        assert_eq!(node.component::<Name>(), Some(&Name("Link".to_string())));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn universe_serialization() {
+        use crate::serialization::ClassRegistry;
+        use serde::{Deserialize, Serialize};
+
+        // Define some components
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Name(String);
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Age(u32);
+
+        // Define some classes of components
+        define_class! {
+            #[derive(Serialize, Deserialize)]
+            class Owner {
+                name: Name,
+            }
+
+            #[derive(Serialize, Deserialize)]
+            class Pet {
+                name: Name,
+                age: Age,
+            }
+        }
+
+        // Register the classes
+        ClassRegistry::register::<Owner>("Owner");
+        ClassRegistry::register::<Pet>("Pet");
+
+        // Create a universe with an owner node and two pet nodes
+        let mut universe = Universe::new();
+        let owner_node_handle = universe.create_node(
+            None,
+            Owner {
+                name: Name("Jon".to_string()),
+            },
+        );
+        universe.create_node(
+            Some(&owner_node_handle),
+            Pet {
+                name: Name("Garfield".to_string()),
+                age: Age(5),
+            },
+        );
+        universe.create_node(
+            Some(&owner_node_handle),
+            Pet {
+                name: Name("Odie".to_string()),
+                age: Age(3),
+            },
+        );
+
+        // Serialize the universe to JSON and back
+        let json = serde_json::to_string(&universe).unwrap();
+        let deserialized: Universe = serde_json::from_str(&json).unwrap();
+
+        // Assert that the deserialized universe has the same structure
+        let root_handles = deserialized.root_node_handles();
+        assert_eq!(root_handles.len(), 1);
+        let owner_node = deserialized.node(&root_handles[0]).unwrap();
+        assert_eq!(owner_node.class().name(), "Owner");
+        assert_eq!(
+            owner_node.component::<Name>(),
+            Some(&Name("Jon".to_string()))
+        );
+        assert_eq!(
+            deserialized
+                .nodes_with_handles(owner_node.children())
+                .map(|node| {
+                    let node = node.unwrap();
+                    (
+                        node.class().name(),
+                        node.component::<Name>().unwrap(),
+                        node.component::<Age>().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>(),
+            &[
+                ("Pet", &Name("Garfield".to_string()), &Age(5)),
+                ("Pet", &Name("Odie".to_string()), &Age(3))
+            ]
+        );
+
+        // Create a universe with two owner nodes, and swap their order
+        let mut universe = Universe::new();
+        let first_handle = universe.create_node(
+            None,
+            Owner {
+                name: Name("First".to_string()),
+            },
+        );
+        let second_handle = universe.create_node(
+            None,
+            Owner {
+                name: Name("Second".to_string()),
+            },
+        );
+        universe
+            .swap_siblings(&first_handle, &second_handle)
+            .unwrap();
+
+        // Assert that the root nodes keep their order through serialization
+        let json = serde_json::to_string(&universe).unwrap();
+        let deserialized: Universe = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            deserialized
+                .nodes_with_handles(deserialized.root_node_handles())
+                .map(|node| node.unwrap().component::<Name>().unwrap().0.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Second", "First"]
+        );
+    }
+
+    #[test]
+    fn node_disjoint_mutable_lookup() {
+        // Define a component
+        #[derive(Debug, PartialEq)]
+        struct Coins(u32);
+
+        // Define a class of components
+        define_class! {
+            class Wallet {
+                coins: Coins,
+            }
+        }
+
+        // Create a universe with two sibling wallet nodes
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let wallet_handle1 =
+            universe.create_node(Some(&parent_handle), Wallet { coins: Coins(10) });
+        let wallet_handle2 = universe.create_node(Some(&parent_handle), Wallet { coins: Coins(0) });
+
+        // Transfer coins from the first wallet to the second
+        let (wallet1, wallet2) = universe
+            .node_pair_mut(&wallet_handle1, &wallet_handle2)
+            .unwrap();
+        wallet1.component_mut::<Coins>().unwrap().0 -= 4;
+        wallet2.component_mut::<Coins>().unwrap().0 += 4;
+
+        // Assert that both wallets were mutated
+        assert_eq!(
+            universe.node(&wallet_handle1).unwrap().component::<Coins>(),
+            Some(&Coins(6))
+        );
+        assert_eq!(
+            universe.node(&wallet_handle2).unwrap().component::<Coins>(),
+            Some(&Coins(4))
+        );
+
+        // Assert that borrowing the same node twice is rejected
+        assert!(universe
+            .node_pair_mut(&wallet_handle1, &wallet_handle1)
+            .is_none());
+
+        // Assert that the nodes are returned in the order of the handles
+        let nodes = universe.nodes_disjoint_mut(&[
+            wallet_handle2.clone(),
+            parent_handle.clone(),
+            wallet_handle1.clone(),
+        ]);
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| node.as_ref().unwrap().handle())
+                .collect::<Vec<_>>(),
+            &[&wallet_handle2, &parent_handle, &wallet_handle1]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn node_disjoint_mutable_lookup_aliasing() {
+        // Create a universe with a node
+        let mut universe = Universe::new();
+        let node_handle = universe.create_node(None, ());
+
+        // Borrowing the same node twice should panic
+        universe.nodes_disjoint_mut(&[node_handle.clone(), node_handle]);
+    }
+
+    #[test]
+    fn subtree_cloning() {
+        // Define some components
+        #[derive(Debug, PartialEq, Clone)]
+        struct Name(String);
+
+        // Define some classes of components
+        define_class! {
+            #[derive(Clone)]
+            class Part {
+                name: Name,
+            }
+
+            class Keepsake {
+                name: Name,
+            }
+        }
+
+        // Create a universe with a 3-node subtree
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let node_handle1 = universe.create_node(
+            Some(&parent_handle),
+            Part {
+                name: Name("Body".to_string()),
+            },
+        );
+        universe.create_node(
+            Some(&node_handle1),
+            Part {
+                name: Name("Left Arm".to_string()),
+            },
+        );
+        universe.create_node(
+            Some(&node_handle1),
+            Part {
+                name: Name("Right Arm".to_string()),
+            },
+        );
+
+        // Clone the subtree
+        let clone_handle1 = universe.clone_subtree(&node_handle1).unwrap();
+
+        // Assert that the clone was attached to the same parent as the original
+        assert_ne!(clone_handle1, node_handle1);
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().children(),
+            &[node_handle1.clone(), clone_handle1.clone()]
+        );
+
+        // Assert that the clone has distinct handles but identical components
+        let names = |root_handle| {
+            std::iter::once(universe.node(root_handle).unwrap())
+                .chain(universe.descendants(root_handle))
+                .map(|node| (node.handle().clone(), node.component::<Name>().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let original = names(&node_handle1);
+        let clone = names(&clone_handle1);
+        assert_eq!(clone.len(), 3);
+        for ((original_handle, original_name), (clone_handle, clone_name)) in
+            original.iter().zip(clone.iter())
+        {
+            assert_ne!(original_handle, clone_handle);
+            assert_eq!(original_name, clone_name);
+        }
+
+        // Assert that a subtree containing a class which cannot be cloned is not cloned
+        let unique_handle = universe.create_node(
+            Some(&node_handle1),
+            Keepsake {
+                name: Name("Head".to_string()),
+            },
+        );
+        assert!(universe
+            .node(&unique_handle)
+            .unwrap()
+            .class()
+            .clone_box()
+            .is_none());
+        assert_eq!(universe.clone_subtree(&node_handle1), None);
+        assert_eq!(universe.node(&parent_handle).unwrap().children().len(), 2);
+    }
+
+    #[test]
+    fn universe_clearing() {
+        // Create a universe with a few nodes
+        let mut universe = Universe::new();
+        let node_handle1 = universe.create_node(None, ());
+        let node_handle2 = universe.create_node(Some(&node_handle1), ());
+        let node_handle3 = universe.create_node(None, ());
+
+        // Clear the universe
+        universe.clear();
+
+        // Assert that the universe is empty
+        assert_eq!(universe.nodes().count(), 0);
+        assert_eq!(universe.root_node_handles(), &[]);
+
+        // Assert that the old handles no longer point to any node
+        assert!(universe.node(&node_handle1).is_none());
+        assert!(universe.node(&node_handle2).is_none());
+        assert!(universe.node(&node_handle3).is_none());
+
+        // Assert that the universe can be populated again
+        let node_handle4 = universe.create_node(None, ());
+        assert!(universe.node(&node_handle1).is_none());
+        assert_eq!(universe.root_node_handles(), &[node_handle4]);
+    }
+
+    #[test]
+    fn universe_node_count() {
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Assert that the universe is empty
+        assert_eq!(universe.node_count(), 0);
+        assert!(universe.is_empty());
+
+        // Create some nodes
+        let node_handle1 = universe.create_node(None, ());
+        let node_handle2 = universe.create_node(Some(&node_handle1), ());
+        universe.create_node(Some(&node_handle2), ());
+        let node_handle4 = universe.create_node(None, ());
+
+        // Assert that the node count tracks the created nodes
+        assert_eq!(universe.node_count(), 4);
+        assert!(!universe.is_empty());
+
+        // Remove some nodes and assert that the node count tracks the removals
+        universe.remove_node(&node_handle4).unwrap();
+        assert_eq!(universe.node_count(), 3);
+        universe.remove_subtree(&node_handle2);
+        assert_eq!(universe.node_count(), 1);
+        universe.remove_node(&node_handle1).unwrap();
+        assert_eq!(universe.node_count(), 0);
+        assert!(universe.is_empty());
+    }
+
+    #[test]
+    fn node_name_lookup() {
+        // Create a universe with named and unnamed nodes
+        let mut universe = Universe::new();
+        let player_handle = universe.create_node_named(None, (), "Player");
+        let unnamed_handle = universe.create_node(Some(&player_handle), ());
+        let enemy_handle1 = universe.create_node_named(None, (), "Enemy");
+        let enemy_handle2 = universe.create_node_named(None, (), String::from("Enemy"));
+
+        // Assert that the nodes have the expected names
+        assert_eq!(
+            universe.node(&player_handle).unwrap().name_str(),
+            Some("Player")
+        );
+        assert_eq!(universe.node(&unnamed_handle).unwrap().name_str(), None);
+
+        // Assert that nodes can be found by their names
+        assert_eq!(
+            universe.node_by_name("Player").map(|node| node.handle()),
+            Some(&player_handle)
+        );
+        assert_eq!(
+            universe
+                .nodes_by_name("Enemy")
+                .handles()
+                .collect::<Vec<_>>(),
+            &[&enemy_handle1, &enemy_handle2]
+        );
+
+        // Assert that names do not collide with class names
+        assert!(universe.node_by_name("()").is_none());
+        assert!(universe.node_by_name("Nobody").is_none());
+    }
+
+    #[test]
+    fn child_ordering() {
+        // Create a universe with a parent node
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+
+        // Create some children at specific positions
+        let node_handle1 = universe.create_node_at(&parent_handle, 0, ());
+        let node_handle2 = universe.create_node_at(&parent_handle, 0, ());
+        let node_handle3 = universe.create_node_at(&parent_handle, 1, ());
+        let node_handle4 = universe.create_node_at(&parent_handle, 100, ());
+
+        // Assert that the children are in the requested order
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().children(),
+            &[
+                node_handle2.clone(),
+                node_handle3.clone(),
+                node_handle1.clone(),
+                node_handle4.clone()
+            ]
+        );
+
+        // Reorder some of the children
+        assert_eq!(
+            universe.reorder_child(&parent_handle, &node_handle4, 0),
+            Some(())
+        );
+        assert_eq!(
+            universe.reorder_child(&parent_handle, &node_handle2, 100),
+            Some(())
+        );
+
+        // Assert that the children reflect the reordering
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().children(),
+            &[
+                node_handle4.clone(),
+                node_handle3,
+                node_handle1,
+                node_handle2
+            ]
+        );
+
+        // Assert that reordering a node which is not a child fails
+        assert_eq!(
+            universe.reorder_child(&node_handle4, &parent_handle, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn subtree_transfer() {
+        // Define a component
+        #[derive(Debug, PartialEq)]
+        struct Name(String);
+
+        // Define a class of components
+        define_class! {
+            class Room {
+                name: Name,
+            }
+        }
+
+        // Create a source universe with a subtree
+        let mut source = Universe::new();
+        let source_root_handle = source.create_node(None, ());
+        let node_handle1 = source.create_node(
+            Some(&source_root_handle),
+            Room {
+                name: Name("Hall".to_string()),
+            },
+        );
+        let node_handle2 = source.create_node(
+            Some(&node_handle1),
+            Room {
+                name: Name("Kitchen".to_string()),
+            },
+        );
+
+        // Create a destination universe
+        let mut dest = Universe::new();
+        let dest_root_handle = dest.create_node(None, ());
+
+        // Transfer the subtree to the destination universe
+        let new_handle1 = source
+            .transfer_subtree(&node_handle1, &mut dest, Some(&dest_root_handle))
+            .unwrap();
+
+        // Assert that the subtree is gone from the source universe
+        assert!(source.node(&node_handle1).is_none());
+        assert!(source.node(&node_handle2).is_none());
+        assert_eq!(source.node_count(), 1);
+        assert_eq!(source.node(&source_root_handle).unwrap().children(), &[]);
+
+        // Assert that the subtree is present in the destination universe
+        assert_eq!(dest.node_count(), 3);
+        assert_eq!(
+            dest.node(&dest_root_handle).unwrap().children(),
+            &[new_handle1.clone()]
+        );
+        let new_node1 = dest.node(&new_handle1).unwrap();
+        assert_eq!(new_node1.handle(), &new_handle1);
+        assert_eq!(new_node1.parent(), Some(&dest_root_handle));
+        assert_eq!(
+            new_node1.component::<Name>(),
+            Some(&Name("Hall".to_string()))
+        );
+        let new_node2 = dest.node(&new_node1.children()[0]).unwrap();
+        assert_eq!(new_node2.parent(), Some(&new_handle1));
+        assert_eq!(
+            new_node2.component::<Name>(),
+            Some(&Name("Kitchen".to_string()))
+        );
+
+        // Assert that transferring a removed subtree fails
+        assert_eq!(
+            source.transfer_subtree(&node_handle1, &mut dest, None),
+            None
+        );
+    }
+
+    #[test]
+    fn universe_merging() {
+        // Create a universe with a node
+        let mut universe = Universe::new();
+        let node_handle = universe.create_node_named(None, (), "Existing");
+
+        // Create another universe with two trees
+        let mut other = Universe::new();
+        let other_root_handle1 = other.create_node_named(None, (), "Root 1");
+        other.create_node_named(Some(&other_root_handle1), (), "Child 1");
+        other.create_node_named(Some(&other_root_handle1), (), "Child 2");
+        other.create_node_named(None, (), "Root 2");
+
+        // Merge the other universe into the first
+        let new_root_handles = universe.merge_from(other);
+
+        // Assert that the combined universe contains every node
+        assert_eq!(universe.node_count(), 5);
+        assert!(universe.node(&node_handle).is_some());
+
+        // Assert that the merged roots are roots in the combined universe
+        assert_eq!(new_root_handles.len(), 2);
+        let new_root1 = universe.node(&new_root_handles[0]).unwrap();
+        let new_root2 = universe.node(&new_root_handles[1]).unwrap();
+        assert_eq!(new_root1.name_str(), Some("Root 1"));
+        assert_eq!(new_root1.parent(), None);
+        assert_eq!(new_root2.name_str(), Some("Root 2"));
+        assert_eq!(new_root2.parent(), None);
+
+        // Assert that the hierarchy was preserved
+        assert_eq!(
+            universe
+                .nodes_with_handles(new_root1.children())
+                .map(|node| node.unwrap().name_str().unwrap())
+                .collect::<Vec<_>>(),
+            &["Child 1", "Child 2"]
+        );
+        assert_eq!(new_root2.children(), &[]);
+
+        // Create another universe whose roots are not in slot order
+        let mut other = Universe::new();
+        let removed_handle = other.create_node(None, ());
+        other.create_node_named(None, (), "Root 3");
+        other.remove_node(&removed_handle).unwrap();
+        other.create_node_named(None, (), "Root 4");
+
+        // Assert that merging it keeps the order of its roots
+        let new_root_handles = universe.merge_from(other);
+        assert_eq!(
+            universe
+                .nodes_with_handles(&new_root_handles)
+                .map(|node| node.unwrap().name_str().unwrap())
+                .collect::<Vec<_>>(),
+            &["Root 3", "Root 4"]
+        );
+    }
+
+    #[test]
+    fn root_promotion() {
+        // Create a universe with a 3-deep chain of nodes
+        let mut universe = Universe::new();
+        let node_handle1 = universe.create_node(None, ());
+        let node_handle2 = universe.create_node(Some(&node_handle1), ());
+        let node_handle3 = universe.create_node(Some(&node_handle2), ());
+
+        // Assert that only node 1 is a root
+        assert!(universe.is_root(&node_handle1));
+        assert!(!universe.is_root(&node_handle3));
+        assert_eq!(universe.root_node_handles(), &[node_handle1.clone()]);
+
+        // Make the deepest node a root, twice
+        universe.make_root(&node_handle3).unwrap();
+        universe.make_root(&node_handle3).unwrap();
+
+        // Assert that is_root and root_node_handles agree
+        assert!(universe.is_root(&node_handle3));
+        assert_eq!(
+            universe.root_node_handles(),
+            &[node_handle1.clone(), node_handle3.clone()]
+        );
+        assert_eq!(universe.node(&node_handle2).unwrap().children(), &[]);
+
+        // Move node 1 beneath node 3 and assert that it is no longer a root
+        universe
+            .change_parent(&node_handle1, Some(&node_handle3))
+            .unwrap();
+        assert!(!universe.is_root(&node_handle1));
+        assert_eq!(universe.root_node_handles(), &[node_handle3]);
+    }
+
+    #[test]
+    fn class_component_enumeration() {
+        use std::any::TypeId;
+
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Name(String);
+        #[derive(Debug, PartialEq)]
+        struct Age(u32);
+
+        // Define a class of components
+        define_class! {
+            class Cat {
+                name: Name,
+                age: Age,
+            }
+        }
+
+        // Create a universe with a cat node and an empty node
+        let mut universe = Universe::new();
+        let cat_node_handle = universe.create_node(
+            None,
+            Cat {
+                name: Name("Garfield".to_string()),
+                age: Age(5),
+            },
+        );
+        let empty_node_handle = universe.create_node(None, ());
+        let cat_node = universe.node(&cat_node_handle).unwrap();
+
+        // Assert that the class's components are enumerated in field order
+        assert_eq!(
+            cat_node.component_types().collect::<Vec<_>>(),
+            &[("name", TypeId::of::<Name>()), ("age", TypeId::of::<Age>())]
+        );
+
+        // Assert that the component values are enumerated too
+        let mut components = cat_node.class().components_iter();
+        let (_, _, name) = components.next().unwrap();
+        assert_eq!(
+            name.downcast_ref::<Name>(),
+            Some(&Name("Garfield".to_string()))
+        );
+        let (_, _, age) = components.next().unwrap();
+        assert_eq!(age.downcast_ref::<Age>(), Some(&Age(5)));
+        assert!(components.next().is_none());
+
+        // Assert that the empty class has no components
+        assert_eq!(
+            universe
+                .node(&empty_node_handle)
+                .unwrap()
+                .component_types()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn tuple_class_component_lookup() {
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Name(String);
+        #[derive(Debug, PartialEq)]
+        struct Age(u32);
+
+        // Define some tuple classes of components alongside a named class
+        define_class! {
+            class Cat(Name, Age);
+
+            class Dog {
+                name: Name,
+            }
+
+            class Fish(Name,);
+        }
+
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a cat node, a dog node and a fish node
+        let cat_node_handle = universe.create_node(None, Cat(Name("Garfield".to_string()), Age(5)));
+        universe.create_node(
+            None,
+            Dog {
+                name: Name("Odie".to_string()),
+            },
+        );
+        let fish_node_handle = universe.create_node(None, Fish(Name("Nemo".to_string())));
+
+        // Assert that the cat node has both of its components
+        let cat_node = universe.node(&cat_node_handle).unwrap();
+        assert_eq!(
+            cat_node.component::<Name>(),
+            Some(&Name("Garfield".to_string()))
+        );
+        assert_eq!(cat_node.component::<Age>(), Some(&Age(5)));
+        assert_eq!(cat_node.class().name(), "Cat");
+
+        // Assert that the fish node has only a Name component
+        let fish_node = universe.node_mut(&fish_node_handle).unwrap();
+        assert_eq!(fish_node.component::<Age>(), None);
+        fish_node.component_mut::<Name>().unwrap().0 = "Dory".to_string();
+        assert_eq!(
+            fish_node.class_as::<Fish>().unwrap().0,
+            Name("Dory".to_string())
+        );
+
+        // Test search the universe for nodes with both a Name and an Age component
+        assert_eq!(
+            universe
+                .nodes()
+                .with_components::<(Name, Age)>()
+                .map(|(node, _name, _age)| node)
+                .handles()
+                .collect::<Vec<_>>(),
+            &[&cat_node_handle]
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_class_component_lookup() {
+        // Define some components
+        #[derive(Debug, Clone, PartialEq)]
+        struct Name(String);
+        #[derive(Debug, Clone, PartialEq)]
+        struct Age(u32);
+
+        // Derive a class with two components and one skipped field
+        #[derive(Clone, crate::Class)]
+        struct Cat {
+            name: Name,
+            age: Age,
+            #[class(skip)]
+            #[allow(dead_code)]
+            lives: u8,
+        }
+
+        // Create a universe
+        let mut universe = Universe::new();
+
+        // Create a cat node
+        let cat_node_handle = universe.create_node(
+            None,
+            Cat {
+                name: Name("Garfield".to_string()),
+                age: Age(5),
+                lives: 9,
+            },
+        );
+
+        // Assert that the cat node has both of its components
+        let cat_node = universe.node(&cat_node_handle).unwrap();
+        assert_eq!(cat_node.class().name(), "Cat");
+        assert_eq!(
+            cat_node.component::<Name>(),
+            Some(&Name("Garfield".to_string()))
+        );
+        assert_eq!(cat_node.component::<Age>(), Some(&Age(5)));
+
+        // Assert that the skipped field is not a component
+        assert_eq!(cat_node.component::<u8>(), None);
+        let field_names = cat_node
+            .class()
+            .components_iter()
+            .map(|(name, _, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(field_names, vec!["name", "age"]);
+
+        // Assert that components can be mutated through the derived class
+        universe
+            .node_mut(&cat_node_handle)
+            .unwrap()
+            .component_mut::<Age>()
+            .unwrap()
+            .0 = 6;
+        assert_eq!(
+            universe.node(&cat_node_handle).unwrap().component::<Age>(),
+            Some(&Age(6))
+        );
+
+        // Assert that the derived class can be cloned
+        let clone_handle = universe.clone_subtree(&cat_node_handle).unwrap();
+        assert_eq!(
+            universe.node(&clone_handle).unwrap().component::<Name>(),
+            Some(&Name("Garfield".to_string()))
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_node_iteration() {
+        use rayon::prelude::*;
+
+        // Define a component
+        struct Score(u64);
+
+        // Define a class with the component
+        define_class! {
+            class Player {
+                score: Score,
+            }
+        }
+
+        // Create a universe with many player nodes, plus a node without a score
+        let mut universe = Universe::new();
+        for score in 0..1000 {
+            universe.create_node(
+                None,
+                Player {
+                    score: Score(score),
+                },
+            );
+        }
+        universe.create_node(None, ());
+
+        // Assert that summing the scores in parallel matches the serial sum
+        let serial_sum: u64 = universe
+            .nodes()
+            .with_component::<Score>()
+            .map(|(_, score)| score.0)
+            .sum();
+        let parallel_sum: u64 = universe
+            .par_nodes()
+            .filter_map(|node| node.component::<Score>())
+            .map(|score| score.0)
+            .sum();
+        assert_eq!(parallel_sum, serial_sum);
+
+        // Double every score in parallel
+        universe.par_nodes_mut().for_each(|node| {
+            if let Some(score) = node.component_mut::<Score>() {
+                score.0 *= 2;
+            }
+        });
+
+        // Assert that every score was doubled exactly once
+        let doubled_sum: u64 = universe
+            .nodes()
+            .with_component::<Score>()
+            .map(|(_, score)| score.0)
+            .sum();
+        assert_eq!(doubled_sum, serial_sum * 2);
+    }
+
+    #[test]
+    fn class_index_lookup() {
+        use std::collections::HashSet;
+
+        // Define some classes
+        define_class! {
+            class Cat {
+                lives: u8,
+            }
+
+            class Dog {
+                age: u32,
+            }
+        }
+
+        // Create a universe with a mixed population of nodes
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let mut cat_handles = Vec::new();
+        for i in 0..10 {
+            cat_handles.push(universe.create_node(Some(&root_handle), Cat { lives: i }));
+            universe.create_node(Some(&root_handle), Dog { age: i as u32 });
+        }
+
+        // Remove some cats, and a subtree containing the rest of the population
+        universe.remove_node(&cat_handles[0]).unwrap();
+        let subtree_handle = universe.create_node(None, ());
+        universe
+            .change_parent(&cat_handles[1], Some(&subtree_handle))
+            .unwrap();
+        universe.remove_subtree(&subtree_handle);
+
+        // Assert that the indexed path returns the same nodes as the scanning path
+        let indexed = universe
+            .nodes_of_class::<Cat>()
+            .map(|node| node.handle().clone())
+            .collect::<HashSet<_>>();
+        let scanned = universe
+            .nodes()
+            .with_class::<Cat>()
+            .map(|node| node.handle().clone())
+            .collect::<HashSet<_>>();
+        assert_eq!(indexed, scanned);
+        assert_eq!(indexed.len(), 8);
+        assert_eq!(universe.nodes_of_class::<Dog>().count(), 10);
+        assert_eq!(universe.nodes_of_class::<()>().count(), 1);
+
+        // Assert that clearing the universe clears the index
+        universe.clear();
+        assert_eq!(universe.nodes_of_class::<Dog>().count(), 0);
+    }
+
+    #[test]
+    fn component_index_lookup() {
+        use std::collections::HashSet;
+
+        // Define some components
+        struct Position(i32);
+        struct Velocity(i32);
+
+        // Define some classes using the components
+        define_class! {
+            class Rock {
+                position: Position,
+            }
+
+            class Ball {
+                position: Position,
+                velocity: Velocity,
+            }
+        }
+
+        // Create a universe with a mixed population of nodes
+        let mut universe = Universe::new();
+        let rock_handle = universe.create_node(
+            None,
+            Rock {
+                position: Position(0),
+            },
+        );
+        let ball_handle = universe.create_node(
+            None,
+            Ball {
+                position: Position(1),
+                velocity: Velocity(2),
+            },
+        );
+        let empty_handle = universe.create_node(None, ());
+
+        // Give the empty node and the rock a velocity dynamically
+        universe.insert_component(&empty_handle, Velocity(3));
+        universe.insert_component(&rock_handle, Velocity(4));
+
+        // Assert that the indexed path returns the same nodes as the scanning path
+        let indexed_handles = |universe: &Universe| {
+            universe
+                .nodes_with_component_indexed::<Velocity>()
+                .map(|(node, _)| node.handle().clone())
+                .collect::<HashSet<_>>()
+        };
+        let scanned = universe
+            .nodes()
+            .with_component::<Velocity>()
+            .map(|(node, _)| node.handle().clone())
+            .collect::<HashSet<_>>();
+        assert_eq!(indexed_handles(&universe), scanned);
+        assert_eq!(scanned.len(), 3);
+        assert_eq!(
+            universe.nodes_with_component_indexed::<Position>().count(),
+            2
+        );
+
+        // Remove the rock's velocity, and assert that the index is updated
+        assert_eq!(
+            universe
+                .remove_component::<Velocity>(&rock_handle)
+                .map(|velocity| velocity.0),
+            Some(4)
+        );
+        assert_eq!(
+            indexed_handles(&universe),
+            HashSet::from([ball_handle.clone(), empty_handle.clone()])
+        );
+
+        // Remove the ball node, and assert that the index is updated
+        universe.remove_node(&ball_handle).unwrap();
+        assert_eq!(
+            indexed_handles(&universe),
+            HashSet::from([empty_handle.clone()])
+        );
+        let positions = universe
+            .nodes_with_component_indexed::<Position>()
+            .map(|(_, position)| position.0)
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![0]);
+    }
+
+    #[test]
+    fn batch_node_creation() {
+        // Define a class
+        define_class! {
+            class Item {
+                index: usize,
+            }
+        }
+
+        // Create a universe with a parent node
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+
+        // Create 100 children at once
+        let child_handles =
+            universe.create_nodes(Some(&parent_handle), (0..100).map(|index| Item { index }));
+
+        // Assert that the children were attached to the parent in order
+        let parent = universe.node(&parent_handle).unwrap();
+        assert_eq!(parent.children().len(), 100);
+        assert_eq!(parent.children(), child_handles.as_slice());
+        for (index, child_handle) in child_handles.iter().enumerate() {
+            let child = universe.node(child_handle).unwrap();
+            assert_eq!(child.parent(), Some(&parent_handle));
+            assert_eq!(child.component::<usize>(), Some(&index));
+        }
+
+        // Create some roots at once, and assert that they are roots
+        let root_handles = universe.create_nodes(None, [(), ()]);
+        assert!(root_handles.iter().all(|handle| universe.is_root(handle)));
+        assert_eq!(universe.node_count(), 103);
+    }
+
+    #[test]
+    fn children_node_lookup() {
+        // Define a class
+        define_class! {
+            class Counter {
+                count: u32,
+            }
+        }
+
+        // Create a universe with a parent node and some children
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let child_handles =
+            universe.create_nodes(Some(&parent_handle), (0..3).map(|count| Counter { count }));
+
+        // Assert that the iterated child nodes match the handles from children()
+        let iterated_handles = universe
+            .children_nodes(&parent_handle)
+            .map(|node| node.handle().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            iterated_handles,
+            universe.node(&parent_handle).unwrap().children()
+        );
+        assert_eq!(iterated_handles, child_handles);
+
+        // Increment every child's counter through mutable references
+        for child in universe.children_nodes_mut(&parent_handle) {
+            *child.component_mut::<u32>().unwrap() += 10;
+        }
+
+        // Assert that every child was mutated
+        let counts = universe
+            .children_nodes(&parent_handle)
+            .map(|node| *node.component::<u32>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![10, 11, 12]);
+
+        // Assert that a leaf node has no child nodes
+        assert_eq!(universe.children_nodes(&child_handles[0]).count(), 0);
+    }
+
+    #[test]
+    fn nth_child_lookup() {
+        // Create a universe with a node with no children, one child, and three children
+        let mut universe = Universe::new();
+        let empty_handle = universe.create_node(None, ());
+        let single_handle = universe.create_node(None, ());
+        let single_child_handle = universe.create_node(Some(&single_handle), ());
+        let multi_handle = universe.create_node(None, ());
+        let multi_child_handles = universe.create_nodes(Some(&multi_handle), [(), (), ()]);
+
+        // Assert that the node with no children has no first or nth child
+        assert_eq!(universe.node(&empty_handle).unwrap().child_count(), 0);
+        assert_eq!(universe.first_child(&empty_handle), None);
+        assert_eq!(universe.nth_child(&empty_handle, 0), None);
+
+        // Assert that the node with one child returns it as its first child
+        assert_eq!(universe.node(&single_handle).unwrap().child_count(), 1);
+        assert_eq!(
+            universe
+                .first_child(&single_handle)
+                .map(|node| node.handle()),
+            Some(&single_child_handle)
+        );
+        assert_eq!(universe.nth_child(&single_handle, 1), None);
+
+        // Assert that the node with three children returns each of them in order
+        assert_eq!(universe.node(&multi_handle).unwrap().child_count(), 3);
+        for (n, child_handle) in multi_child_handles.iter().enumerate() {
+            assert_eq!(
+                universe
+                    .nth_child(&multi_handle, n)
+                    .map(|node| node.handle()),
+                Some(child_handle)
+            );
+        }
+        assert_eq!(
+            universe
+                .first_child(&multi_handle)
+                .map(|node| node.handle()),
+            Some(&multi_child_handles[0])
+        );
+        assert_eq!(universe.nth_child(&multi_handle, 3), None);
+    }
+
+    #[test]
+    fn nearest_ancestor_lookup() {
+        // Define a component
+        #[derive(Debug, PartialEq)]
+        struct Transform(i32);
+
+        // Define a class with the component
+        define_class! {
+            class Group {
+                transform: Transform,
+            }
+        }
+
+        // Create a universe where only the grandparent and the node itself have the component
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let grandparent_handle = universe.create_node(
+            Some(&root_handle),
+            Group {
+                transform: Transform(1),
+            },
+        );
+        let parent_handle = universe.create_node(Some(&grandparent_handle), ());
+        let node_handle = universe.create_node(
+            Some(&parent_handle),
+            Group {
+                transform: Transform(2),
+            },
+        );
+
+        // Assert that the grandparent is found, and not the node itself
+        let (ancestor, transform) = universe
+            .nearest_ancestor_with_component::<Transform>(&node_handle)
+            .unwrap();
+        assert_eq!(ancestor.handle(), &grandparent_handle);
+        assert_eq!(transform, &Transform(1));
+        let (ancestor, group) = universe
+            .nearest_ancestor_with_class::<Group>(&node_handle)
+            .unwrap();
+        assert_eq!(ancestor.handle(), &grandparent_handle);
+        assert_eq!(group.transform, Transform(1));
+
+        // Assert that nothing is found above the grandparent
+        assert!(universe
+            .nearest_ancestor_with_component::<Transform>(&grandparent_handle)
+            .is_none());
+        assert!(universe
+            .nearest_ancestor_with_class::<Group>(&grandparent_handle)
+            .is_none());
+    }
+
+    #[test]
+    fn node_lifecycle_observers() {
+        use std::{cell::RefCell, rc::Rc};
+
+        // Create a universe with an observer for each event, recording the handles they receive
+        let mut universe = Universe::new();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let created_events = events.clone();
+        universe.on_node_created(move |handle| {
+            created_events
+                .borrow_mut()
+                .push(("created", handle.clone()))
+        });
+        let removed_events = events.clone();
+        universe.on_node_removed(move |handle| {
+            removed_events
+                .borrow_mut()
+                .push(("removed", handle.clone()))
+        });
+        let reparented_events = events.clone();
+        universe.on_reparented(move |handle| {
+            reparented_events
+                .borrow_mut()
+                .push(("reparented", handle.clone()))
+        });
+
+        // Create, reparent and remove some nodes
+        let node_handle1 = universe.create_node(None, ());
+        let node_handle2 = universe.create_node(None, ());
+        universe
+            .change_parent(&node_handle2, Some(&node_handle1))
+            .unwrap();
+        universe.remove_node(&node_handle1).unwrap();
+
+        // Assert that the observers received the expected handles in order
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                ("created", node_handle1.clone()),
+                ("created", node_handle2.clone()),
+                ("reparented", node_handle2.clone()),
+                ("removed", node_handle1.clone()),
+                ("reparented", node_handle2.clone()),
+            ]
+        );
+
+        // Assert that a failed reparent does not notify the observers
+        events.borrow_mut().clear();
+        let node_handle3 = universe.create_node(Some(&node_handle2), ());
+        assert!(universe
+            .change_parent(&node_handle2, Some(&node_handle3))
+            .is_err());
+        assert_eq!(*events.borrow(), vec![("created", node_handle3.clone())]);
+    }
+
+    #[test]
+    fn component_change_detection() {
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Position(i32);
+        struct Velocity;
+
+        // Define a class with the components
+        define_class! {
+            class Ball {
+                position: Position,
+                velocity: Velocity,
+            }
+        }
+
+        // Create a universe with some ball nodes
+        let mut universe = Universe::new();
+        let ball_handles = universe.create_nodes(
+            None,
+            (0..3).map(|i| Ball {
+                position: Position(i),
+                velocity: Velocity,
+            }),
+        );
+
+        // Mutate the position of one of the balls
+        universe
+            .node_mut(&ball_handles[1])
+            .unwrap()
+            .component_mut::<Position>()
+            .unwrap()
+            .0 += 10;
+
+        // Assert that only that ball's position shows up as changed
+        let changed = universe
+            .nodes()
+            .changed::<Position>()
+            .map(|(node, position)| (node.handle().clone(), position.0))
+            .collect::<Vec<_>>();
+        assert_eq!(changed, vec![(ball_handles[1].clone(), 11)]);
+        assert_eq!(universe.nodes().changed::<Velocity>().count(), 0);
+
+        // Assert that clearing the change ticks resets the changed state
+        universe.clear_change_ticks();
+        assert_eq!(universe.nodes().changed::<Position>().count(), 0);
+        assert!(!universe
+            .node(&ball_handles[1])
+            .unwrap()
+            .is_changed::<Position>());
+    }
+
+    #[test]
+    fn node_relationships() {
+        // Define some relationship types
+        struct Targets;
+        struct OwnedBy;
+
+        // Create a universe with some nodes
+        let mut universe = Universe::new();
+        let player_handle = universe.create_node(None, ());
+        let enemy_handle = universe.create_node(None, ());
+        let sword_handle = universe.create_node(None, ());
+
+        // Relate the nodes with two different relationship types
+        universe.relate::<Targets>(&player_handle, &enemy_handle);
+        universe.relate::<Targets>(&player_handle, &sword_handle);
+        universe.relate::<Targets>(&player_handle, &enemy_handle);
+        universe.relate::<OwnedBy>(&sword_handle, &player_handle);
+        universe.relate::<OwnedBy>(&player_handle, &enemy_handle);
+
+        // Assert that each relationship type is tracked independently
+        assert_eq!(
+            universe.related::<Targets>(&player_handle),
+            &[enemy_handle.clone(), sword_handle.clone()]
+        );
+        assert_eq!(
+            universe.related::<OwnedBy>(&player_handle),
+            &[enemy_handle.clone()]
+        );
+        assert_eq!(
+            universe.related::<OwnedBy>(&sword_handle),
+            &[player_handle.clone()]
+        );
+        assert!(universe.related::<Targets>(&sword_handle).is_empty());
+
+        // Unrelate the player from the enemy, and assert that only that relationship is removed
+        assert_eq!(
+            universe.unrelate::<Targets>(&player_handle, &enemy_handle),
+            Some(())
+        );
+        assert_eq!(
+            universe.unrelate::<Targets>(&player_handle, &enemy_handle),
+            None
+        );
+        assert_eq!(
+            universe.related::<Targets>(&player_handle),
+            &[sword_handle.clone()]
+        );
+        assert_eq!(
+            universe.related::<OwnedBy>(&player_handle),
+            &[enemy_handle.clone()]
+        );
+
+        // Remove the sword, and assert that relationships from and to it are removed
+        universe.remove_node(&sword_handle).unwrap();
+        assert!(universe.related::<Targets>(&player_handle).is_empty());
+        assert!(universe.related::<OwnedBy>(&sword_handle).is_empty());
+    }
+
+    #[test]
+    fn subtree_relationships() {
+        // Define a relationship type
+        struct Targets;
+
+        // Create a universe with a subtree whose nodes are related to each other and to an outside node
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(Some(&root_handle), ());
+        let outside_handle = universe.create_node(None, ());
+        universe.relate::<Targets>(&root_handle, &child_handle);
+        universe.relate::<Targets>(&child_handle, &outside_handle);
+
+        // Clone the subtree, and assert that only the relationships within it are copied
+        let clone_handle = universe.clone_subtree(&root_handle).unwrap();
+        let clone_child_handle = universe.node(&clone_handle).unwrap().children()[0].clone();
+        assert_eq!(
+            universe.related::<Targets>(&clone_handle),
+            &[clone_child_handle.clone()]
+        );
+        assert!(universe.related::<Targets>(&clone_child_handle).is_empty());
+
+        // Transfer the original subtree, and assert that the relationships within it are moved
+        let mut dest = Universe::new();
+        let new_root_handle = universe
+            .transfer_subtree(&root_handle, &mut dest, None)
+            .unwrap();
+        let new_child_handle = dest.node(&new_root_handle).unwrap().children()[0].clone();
+        assert_eq!(
+            dest.related::<Targets>(&new_root_handle),
+            &[new_child_handle.clone()]
+        );
+        assert!(dest.related::<Targets>(&new_child_handle).is_empty());
+
+        // Relate two separate roots, then merge their universe, and assert that the relationship is kept
+        let mut other = Universe::new();
+        let first_handle = other.create_node(None, ());
+        let second_handle = other.create_node(None, ());
+        other.relate::<Targets>(&second_handle, &first_handle);
+        let merged_handles = dest.merge_from(other);
+        assert_eq!(
+            dest.related::<Targets>(&merged_handles[1]),
+            &[merged_handles[0].clone()]
+        );
+        assert!(dest.related::<Targets>(&merged_handles[0]).is_empty());
+        assert_eq!(
+            dest.related::<Targets>(&new_root_handle),
+            &[new_child_handle.clone()]
+        );
+    }
+
+    #[cfg(feature = "petgraph")]
+    #[test]
+    fn petgraph_export() {
+        // Create a universe with a small tree, plus a separate root
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handles = universe.create_nodes(Some(&root_handle), [(), ()]);
+        let grandchild_handle = universe.create_node(Some(&child_handles[0]), ());
+        universe.create_node(None, ());
+
+        // Convert the universe into a graph
+        let (graph, indices) = universe.to_petgraph();
+
+        // Assert that the graph has a node for each universe node and an edge for each parent/child link
+        assert_eq!(graph.node_count(), 5);
+        assert_eq!(graph.edge_count(), 3);
+        assert_eq!(indices.len(), 5);
+        assert_eq!(graph[indices[&grandchild_handle]], grandchild_handle);
+        assert!(graph.contains_edge(indices[&root_handle], indices[&child_handles[1]]));
+        assert!(graph.contains_edge(indices[&child_handles[0]], indices[&grandchild_handle]));
+
+        // Assert that the graph is acyclic
+        assert!(!petgraph::algo::is_cyclic_directed(&graph));
+    }
+
+    #[test]
+    fn dot_export() {
+        // Define a class
+        define_class! {
+            class Item {
+                count: u32,
+            }
+        }
+
+        // Create a universe with a known tree of 4 nodes and 3 edges
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(Some(&root_handle), Item { count: 1 });
+        universe.create_node(Some(&root_handle), Item { count: 2 });
+        universe.create_node(Some(&child_handle), Item { count: 3 });
+
+        // Assert that the DOT output has a node statement per node and an edge per parent/child link
+        let dot = universe.to_dot();
+        assert!(dot.starts_with("digraph {"));
+        assert_eq!(dot.matches("->").count(), 3);
+        assert_eq!(dot.matches("[label=").count(), 4);
+        assert_eq!(dot.matches("Item").count(), 3);
+
+        // Assert that nodes can be labeled with a custom summary
+        let dot = universe.to_dot_with(|node| match node.component::<u32>() {
+            Some(count) => format!("count = {}", count),
+            None => "\"root\"".to_string(),
+        });
+        assert!(dot.contains("[label=\"count = 3\"]"));
+        assert!(dot.contains("[label=\"\\\"root\\\"\"]"));
+    }
+
+    #[test]
+    fn typed_handle_lookup() {
+        use crate::handle::TypedHandle;
+
+        // Define some classes
+        define_class! {
+            class Cat {
+                lives: u8,
+            }
+
+            class Dog {
+                age: u32,
+            }
+        }
+
+        // Create a universe with a cat node and a dog node
+        let mut universe = Universe::new();
+        let cat_handle: TypedHandle<Cat> = universe.create_node_typed(None, Cat { lives: 9 });
+        let dog_handle: TypedHandle<Dog> =
+            universe.create_node_typed(Some(&cat_handle), Dog { age: 3 });
+
+        // Assert that node_typed returns the concrete class
+        assert_eq!(universe.node_typed(&cat_handle).unwrap().lives, 9);
+        universe.node_typed_mut(&dog_handle).unwrap().age += 1;
+        assert_eq!(universe.node_typed(&dog_handle).unwrap().age, 4);
+
+        // Assert that typed handles can be used as untyped handles
+        assert_eq!(
+            universe.node(&dog_handle).unwrap().parent(),
+            Some(&*cat_handle)
+        );
+        assert!(universe.node(&cat_handle).unwrap().class_is::<Cat>());
+
+        // Assert that node_typed returns None once the node is removed
+        universe.remove_node(&cat_handle).unwrap();
+        assert!(universe.node_typed(&cat_handle).is_none());
+    }
+
+    #[test]
+    fn fallible_node_creation() {
+        // Create a universe with a parent node, then remove the parent
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let child_handle = universe.try_create_node(Some(&parent_handle), ()).unwrap();
+        universe.remove_subtree(&parent_handle);
+        assert!(!universe.contains_node(&child_handle));
+
+        // Assert that creating a node under the removed parent fails
+        let node_count = universe.node_count();
+        assert_eq!(
+            universe.try_create_node(Some(&parent_handle), ()),
+            Err(Error::InvalidParent)
+        );
+
+        // Assert that no orphan node was inserted
+        assert_eq!(universe.node_count(), node_count);
+        assert!(universe.try_create_node(None, ()).is_ok());
+        assert_eq!(universe.node_count(), node_count + 1);
+    }
+
+    #[test]
+    fn subtree_mutation() {
+        // Define a class with a counter
+        define_class! {
+            class Counter {
+                count: u32,
+            }
+        }
+
+        // Create a universe with a subtree of counters, plus a counter outside of it
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, Counter { count: 0 });
+        let child_handles = universe.create_nodes(
+            Some(&root_handle),
+            [Counter { count: 0 }, Counter { count: 0 }],
+        );
+        universe.create_node(Some(&child_handles[0]), Counter { count: 0 });
+        let outside_handle = universe.create_node(None, Counter { count: 0 });
+
+        // Increment the counter of every node in the subtree, recording the visit order
+        let mut visited = Vec::new();
+        universe.for_each_in_subtree_mut(&root_handle, |node| {
+            *node.component_mut::<u32>().unwrap() += 1;
+            visited.push(node.handle().clone());
+        });
+
+        // Assert that every node in the subtree was visited exactly once, in depth-first pre-order
+        let mut expected = vec![root_handle.clone()];
+        expected.extend(universe.descendants(&root_handle).handles().cloned());
+        assert_eq!(visited, expected);
+        assert!(universe
+            .descendants(&root_handle)
+            .all(|node| node.component::<u32>() == Some(&1)));
+        assert_eq!(
+            universe.node(&root_handle).unwrap().component::<u32>(),
+            Some(&1)
+        );
+
+        // Assert that the node outside of the subtree was not visited
+        assert_eq!(
+            universe.node(&outside_handle).unwrap().component::<u32>(),
+            Some(&0)
+        );
+    }
+
+    #[test]
+    fn descendant_folding() {
+        // Define a class with a mass
+        define_class! {
+            class Body {
+                mass: u32,
+            }
+        }
+
+        // Create a universe with a tree of bodies
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, Body { mass: 100 });
+        let child_handle = universe.create_node_named(Some(&root_handle), Body { mass: 1 }, "a");
+        universe.create_node_named(Some(&child_handle), Body { mass: 2 }, "b");
+        universe.create_node_named(Some(&root_handle), (), "c");
+        universe.create_node_named(Some(&root_handle), Body { mass: 4 }, "d");
+
+        // Assert that the masses of the descendants sum to the hand-computed total
+        let total_mass = universe.fold_descendants(&root_handle, 0, |total, node| {
+            total + node.component::<u32>().copied().unwrap_or(0)
+        });
+        assert_eq!(total_mass, 1 + 2 + 4);
+
+        // Assert that the descendants are visited in depth-first pre-order
+        let names = universe.fold_descendants(&root_handle, String::new(), |names, node| {
+            names + node.name_str().unwrap()
+        });
+        assert_eq!(names, "abcd");
+    }
+
+    #[test]
+    fn node_depth() {
+        // Create a universe with a chain of nodes
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(Some(&root_handle), ());
+        let mut deep_handle = child_handle.clone();
+        for _ in 0..8 {
+            deep_handle = universe.create_node(Some(&deep_handle), ());
+        }
+
+        // Assert that the depths count the parent hops to the root
+        assert_eq!(universe.depth(&root_handle), Some(0));
+        assert_eq!(universe.depth(&child_handle), Some(1));
+        assert_eq!(universe.depth(&deep_handle), Some(9));
+
+        // Assert that removed nodes have no depth
+        universe.remove_subtree(&root_handle);
+        assert_eq!(universe.depth(&deep_handle), None);
+    }
+
+    #[test]
+    fn lowest_common_ancestor() {
+        // Create a universe with two separate trees
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handle1 = universe.create_node(Some(&root_handle), ());
+        let child_handle2 = universe.create_node(Some(&root_handle), ());
+        let grandchild_handle1 = universe.create_node(Some(&child_handle1), ());
+        let grandchild_handle2 = universe.create_node(Some(&child_handle1), ());
+        let other_root_handle = universe.create_node(None, ());
+
+        // Assert that the LCA of siblings is their parent
+        assert_eq!(
+            universe.lowest_common_ancestor(&grandchild_handle1, &grandchild_handle2),
+            Some(child_handle1.clone())
+        );
+        assert_eq!(
+            universe.lowest_common_ancestor(&grandchild_handle1, &child_handle2),
+            Some(root_handle.clone())
+        );
+
+        // Assert that the LCA of an ancestor and its descendant is the ancestor
+        assert_eq!(
+            universe.lowest_common_ancestor(&child_handle1, &grandchild_handle2),
+            Some(child_handle1.clone())
+        );
+        assert_eq!(
+            universe.lowest_common_ancestor(&grandchild_handle2, &root_handle),
+            Some(root_handle.clone())
+        );
+
+        // Assert that nodes in disjoint trees have no LCA
+        assert_eq!(
+            universe.lowest_common_ancestor(&grandchild_handle1, &other_root_handle),
+            None
+        );
+    }
+
+    #[test]
+    fn universe_default() {
+        // Create a universe through Default, including as part of a derived Default
+        #[derive(Default)]
+        struct World {
+            universe: Universe,
+        }
+        let universe = Universe::default();
+        let world = World::default();
+
+        // Assert that both universes are empty
+        assert!(universe.is_empty());
+        assert!(world.universe.is_empty());
+    }
+
+    #[test]
+    fn universe_iteration_by_reference() {
+        // Create a universe with some nodes
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        universe.create_nodes(Some(&root_handle), [(), (), ()]);
+
+        // Name every node by iterating the universe mutably
+        for node in &mut universe {
+            node.__insert_component(String::from("visited"));
+        }
+
+        // Assert that iterating the universe by reference visits every node
+        let mut count = 0;
+        for node in &universe {
+            assert_eq!(node.component::<String>().unwrap(), "visited");
+            count += 1;
+        }
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn node_handle_enumeration() {
+        use std::collections::HashSet;
+
+        // Create a universe with some nodes, then remove one of them
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handles = universe.create_nodes(Some(&root_handle), [(), (), ()]);
+        universe.remove_node(&child_handles[1]).unwrap();
+
+        // Assert that exactly the live handles are yielded
+        let handles = universe.node_handles().collect::<HashSet<_>>();
+        assert_eq!(
+            handles,
+            HashSet::from([
+                root_handle.clone(),
+                child_handles[0].clone(),
+                child_handles[2].clone()
+            ])
+        );
+
+        // Assert that the snapshot of handles can be used while mutating the universe
+        let handles = universe.node_handles().collect::<Vec<_>>();
+        for handle in &handles {
+            universe.remove_node(handle).unwrap();
+        }
+        assert!(universe.is_empty());
+        assert_eq!(universe.node_handles().count(), 0);
+    }
+
+    #[test]
+    fn system_scheduling() {
+        use crate::system::{Query, Schedule};
+
+        // Define some components
+        struct Position(i32);
+        struct Velocity(i32);
+
+        // Define some classes using the components
+        define_class! {
+            class Ball {
+                position: Position,
+                velocity: Velocity,
+            }
+
+            class Wall {
+                position: Position,
+            }
+        }
+
+        // Create a universe with some balls and a wall
+        let mut universe = Universe::new();
+        let ball_handles = universe.create_nodes(
+            None,
+            (1..=3).map(|i| Ball {
+                position: Position(0),
+                velocity: Velocity(i),
+            }),
+        );
+        let wall_handle = universe.create_node(
+            None,
+            Wall {
+                position: Position(0),
+            },
+        );
+
+        // Run a system that doubles every velocity directly
+        universe.run_system(|query: Query<Velocity>| {
+            for velocity in query {
+                velocity.0 *= 2;
+            }
+        });
+
+        // Schedule a system that moves every ball by its velocity, and one that shifts every position
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(|universe: &mut Universe| {
+                for node in universe.nodes_mut().with_class::<Ball>() {
+                    let ball = node.class_as_mut::<Ball>().unwrap();
+                    ball.position.0 += ball.velocity.0;
+                }
+            })
+            .add_query_system(|query: Query<Position>| {
+                for position in query {
+                    position.0 += 100;
+                }
+            });
+
+        // Run the schedule twice, as if for two frames
+        schedule.run(&mut universe);
+        schedule.run(&mut universe);
+
+        // Assert that the systems affected the matching nodes
+        for (i, ball_handle) in ball_handles.iter().enumerate() {
+            let position = universe.node(ball_handle).unwrap().component::<Position>();
+            assert_eq!(position.unwrap().0, 200 + 4 * (i as i32 + 1));
+        }
+        let position = universe.node(&wall_handle).unwrap().component::<Position>();
+        assert_eq!(position.unwrap().0, 200);
+    }
+
+    #[test]
+    fn node_predicate_matching() {
+        // Define some classes
+        define_class! {
+            class Dog {
+                age: u32,
+            }
+
+            class Cat {
+                lives: u8,
+            }
+        }
+
+        // Create a universe with a mixed population
+        let mut universe = Universe::new();
+        universe.create_nodes(None, [3, 5, 3, 7, 3].map(|age| Dog { age }));
+        universe.create_nodes(None, [Cat { lives: 9 }, Cat { lives: 3 }]);
+
+        // Assert that the dogs of age 3 are counted
+        let is_dog_of_age = |age| move |node: &crate::node::Node| matches!(node.class_as::<Dog>(), Some(dog) if dog.age == age);
+        assert_eq!(universe.count_matching(is_dog_of_age(3)), 3);
+        assert_eq!(universe.count_matching(is_dog_of_age(4)), 0);
+
+        // Assert that any_matching and all_matching test the whole population
+        assert!(universe.any_matching(is_dog_of_age(7)));
+        assert!(!universe.any_matching(is_dog_of_age(4)));
+        assert!(!universe.all_matching(|node| node.class_is::<Dog>()));
+        assert!(universe.all_matching(|node| node.class_is::<Dog>() || node.class_is::<Cat>()));
+        assert!(Universe::new().all_matching(|_| false));
+    }
+
+    #[test]
+    fn class_removal() {
+        // Define some classes
+        define_class! {
+            class Dog {
+                age: u32,
+            }
+
+            class Cat {
+                lives: u8,
+            }
+        }
+
+        // Create a universe with a mixed tree, with dogs at every level
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, Dog { age: 1 });
+        let cat_handle1 = universe.create_node(Some(&root_handle), Cat { lives: 1 });
+        let dog_handle = universe.create_node(Some(&cat_handle1), Dog { age: 2 });
+        let puppy_handle = universe.create_node(Some(&dog_handle), Dog { age: 0 });
+        let cat_handle2 = universe.create_node(Some(&puppy_handle), Cat { lives: 2 });
+        let cat_handle3 = universe.create_node(Some(&root_handle), Cat { lives: 3 });
+
+        // Remove all of the dogs
+        assert_eq!(universe.remove_nodes_with_class::<Dog>(), 3);
+
+        // Assert that only the dogs are gone
+        assert_eq!(universe.node_count(), 3);
+        assert_eq!(universe.nodes().with_class::<Dog>().count(), 0);
+        for handle in [&root_handle, &dog_handle, &puppy_handle] {
+            assert!(!universe.contains_node(handle));
+        }
+
+        // Assert that the cats were attached to their nearest remaining ancestors
+        assert!(universe.is_root(&cat_handle1));
+        assert!(universe.is_root(&cat_handle3));
+        assert_eq!(
+            universe.node(&cat_handle2).unwrap().parent(),
+            Some(&cat_handle1)
+        );
+        assert_eq!(universe.remove_nodes_with_class::<Dog>(), 0);
+    }
+
+    #[test]
+    fn node_retention() {
+        // Define a class
+        define_class! {
+            class Number {
+                value: u32,
+            }
+        }
+
+        // Create a universe with a tree of numbers
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, Number { value: 0 });
+        let handle1 = universe.create_node(Some(&root_handle), Number { value: 1 });
+        let handle2 = universe.create_node(Some(&handle1), Number { value: 2 });
+        let handle3 = universe.create_node(Some(&handle2), Number { value: 3 });
+        let handle4 = universe.create_node(Some(&handle3), Number { value: 4 });
+        let handle5 = universe.create_node(Some(&root_handle), Number { value: 5 });
+        let handle6 = universe.create_node(Some(&handle5), Number { value: 6 });
+
+        // Retain only the nodes with an even value
+        universe.retain(|node| node.component::<u32>().unwrap() % 2 == 0);
+
+        // Assert that only the even nodes survive
+        let mut values = universe
+            .nodes()
+            .map(|node| *node.component::<u32>().unwrap())
+            .collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, vec![0, 2, 4, 6]);
+        for handle in [&handle1, &handle3, &handle5] {
+            assert!(!universe.contains_node(handle));
+        }
+
+        // Assert that the survivors were attached to their nearest surviving ancestors
+        let parent = |handle| universe.node(handle).unwrap().parent().cloned();
+        assert_eq!(parent(&root_handle), None);
+        assert_eq!(parent(&handle2), Some(root_handle.clone()));
+        assert_eq!(parent(&handle4), Some(handle2.clone()));
+        assert_eq!(parent(&handle6), Some(root_handle.clone()));
+    }
+
+    #[test]
+    fn sorted_node_iteration() {
+        // Create some nodes, then remove and re-create one so that its slot can be reused
+        let mut universe = Universe::new();
+        let handle_a = universe.create_node_named(None, (), "a");
+        let handle_b = universe.create_node_named(Some(&handle_a), (), "b");
+        universe.create_node_named(None, (), "c");
+        universe.remove_node(&handle_b).unwrap();
+        universe.create_node_named(None, (), "d");
+        universe.create_node_named(Some(&handle_a), (), "e");
+
+        // Assert that the sorted nodes are in creation order
+        let names = universe
+            .nodes_sorted()
+            .map(|node| node.name_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn node_ids() {
+        use std::collections::HashSet;
+
+        // Create a universe with some nodes, removing one of them
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handles = universe.create_nodes(Some(&root_handle), [(), (), ()]);
+        let removed_id = universe.node(&child_handles[1]).unwrap().id();
+        universe.remove_node(&child_handles[1]).unwrap();
+        let new_handle = universe.create_node(None, ());
+
+        // Assert that distinct nodes get distinct ids, and that the removed node's id is not reused
+        let ids = universe
+            .nodes()
+            .map(|node| node.id())
+            .collect::<HashSet<_>>();
+        assert_eq!(ids.len(), 4);
+        assert!(!ids.contains(&removed_id));
+
+        // Assert that the id is stable across clones of the handle
+        let cloned_handle = new_handle.clone();
+        assert_eq!(
+            universe.node(&cloned_handle).unwrap().id(),
+            universe.node(&new_handle).unwrap().id()
+        );
+    }
+
+    #[test]
+    fn handle_from_id_lookup() {
+        // Create a universe with some nodes
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(Some(&root_handle), ());
+
+        // Assert that handles round-trip through their ids
+        for handle in [&root_handle, &child_handle] {
+            let id = universe.node(handle).unwrap().id();
+            assert_eq!(universe.handle_from_id(id).as_ref(), Some(handle));
+        }
+
+        // Assert that the id of a removed node no longer resolves
+        let child_id = universe.node(&child_handle).unwrap().id();
+        universe.remove_node(&child_handle).unwrap();
+        assert_eq!(universe.handle_from_id(child_id), None);
+        assert_eq!(universe.handle_from_id(u64::MAX), None);
+    }
+
+    #[test]
+    fn sibling_navigation() {
+        // Create a universe with a parent of three children, and two roots
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let child_handles = universe.create_nodes(Some(&parent_handle), [(), (), ()]);
+        let other_root_handle = universe.create_node(None, ());
+
+        // Assert that the first child has no previous sibling, and the last child has no next sibling
+        assert!(universe.prev_sibling(&child_handles[0]).is_none());
+        assert!(universe.next_sibling(&child_handles[2]).is_none());
+
+        // Assert that the children are linked to their neighbors
+        let next = |handle| universe.next_sibling(handle).map(|node| node.handle());
+        let prev = |handle| universe.prev_sibling(handle).map(|node| node.handle());
+        assert_eq!(next(&child_handles[0]), Some(&child_handles[1]));
+        assert_eq!(next(&child_handles[1]), Some(&child_handles[2]));
+        assert_eq!(prev(&child_handles[1]), Some(&child_handles[0]));
+        assert_eq!(prev(&child_handles[2]), Some(&child_handles[1]));
+
+        // Assert that root nodes use the other root nodes as their siblings
+        assert_eq!(next(&parent_handle), Some(&other_root_handle));
+        assert_eq!(prev(&other_root_handle), Some(&parent_handle));
+        assert!(universe.prev_sibling(&parent_handle).is_none());
+    }
+
+    #[test]
+    fn subtree_detachment() {
+        // Create a universe with a subtree beneath a parent node
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let subtree_handle = universe.create_node(Some(&parent_handle), ());
+        let child_handles = universe.create_nodes(Some(&subtree_handle), [(), ()]);
+
+        // Detach the subtree
+        assert_eq!(
+            universe.detach(&subtree_handle).unwrap(),
+            Some(parent_handle.clone())
+        );
+
+        // Assert that the subtree became a root with its children preserved
+        assert!(universe.is_root(&subtree_handle));
+        assert!(universe.root_node_handles().contains(&subtree_handle));
+        assert!(universe.node(&parent_handle).unwrap().children().is_empty());
+        assert_eq!(
+            universe.node(&subtree_handle).unwrap().children(),
+            child_handles.as_slice()
+        );
+        assert_eq!(universe.node_count(), 4);
+
+        // Assert that detaching a root node leaves it a root
+        assert_eq!(universe.detach(&subtree_handle).unwrap(), None);
+        assert!(universe.is_root(&subtree_handle));
+
+        // Assert that detaching a removed node fails
+        universe.remove_node(&subtree_handle).unwrap();
+        assert!(matches!(
+            universe.detach(&subtree_handle),
+            Err(Error::InvalidHandle)
+        ));
+        assert!(matches!(
+            universe.make_root(&subtree_handle),
+            Err(Error::InvalidHandle)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Node of class Cat has no component of type u32")]
+    fn node_expect_component() {
+        // Define a class
+        define_class! {
+            class Cat {
+                lives: u8,
+            }
+        }
+
+        // Create a universe with a cat node
+        let mut universe = Universe::new();
+        let cat_handle = universe.create_node(None, Cat { lives: 9 });
+
+        // Assert that an existing component is returned
+        let cat_node = universe.node_mut(&cat_handle).unwrap();
+        assert_eq!(*cat_node.expect_component::<u8>(), 9);
+        *cat_node.expect_component_mut::<u8>() -= 1;
+        assert_eq!(*cat_node.expect_component::<u8>(), 8);
+
+        // Expect a missing component, which should panic
+        cat_node.expect_component::<u32>();
+    }
+
+    #[test]
+    fn node_component_presence() {
+        // Define some classes
+        define_class! {
+            class Cat {
+                lives: u8,
+            }
+
+            class Dog {
+                age: u32,
+            }
+        }
+
+        // Create a universe with a cat node and a dog node
+        let mut universe = Universe::new();
+        let cat_handle = universe.create_node(None, Cat { lives: 9 });
+        let dog_handle = universe.create_node(None, Dog { age: 3 });
+
+        // Assert that each node only has its own class's component
+        let cat_node = universe.node(&cat_handle).unwrap();
+        let dog_node = universe.node(&dog_handle).unwrap();
+        assert!(cat_node.has_component::<u8>());
+        assert!(!cat_node.has_component::<u32>());
+        assert!(dog_node.has_component::<u32>());
+        assert!(!dog_node.has_component::<u8>());
+        assert!(cat_node.has_class::<Cat>());
+        assert!(!cat_node.has_class::<Dog>());
+
+        // Assert that dynamic components are present too
+        universe.insert_component(&cat_handle, 1u32);
+        assert!(universe.node(&cat_handle).unwrap().has_component::<u32>());
+    }
+
+    #[test]
+    fn node_class_component_lookup() {
+        // Define a component
+        struct Speed(u32);
+
+        // Define some classes with the component
+        define_class! {
+            class Car {
+                speed: Speed,
+                gear: u8,
+            }
+
+            class Bike {
+                speed: Speed,
+            }
+        }
+
+        impl Car {
+            fn speed_per_gear(&self) -> u32 {
+                self.speed.0 / self.gear as u32
+            }
+        }
+
+        // Create a universe with a mixed population
+        let mut universe = Universe::new();
+        universe.create_node(
+            None,
+            Car {
+                speed: Speed(60),
+                gear: 3,
+            },
+        );
+        universe.create_node(None, Bike { speed: Speed(20) });
+        let car_handle = universe.create_node(
+            None,
+            Car {
+                speed: Speed(100),
+                gear: 4,
+            },
+        );
+
+        // Give one of the cars a dynamic component
+        universe.insert_component(&car_handle, String::from("fast"));
+
+        // Assert that a class method can be used together with a component
+        let mut results = universe
+            .nodes()
+            .with_class_component::<Car, Speed>()
+            .map(|(car, speed)| (car.speed_per_gear(), speed.0))
+            .collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(results, vec![(20, 60), (25, 100)]);
+
+        // Assert that only nodes with both the class and the component are included
+        let labels = universe
+            .nodes()
+            .with_class_component::<Car, String>()
+            .map(|(car, label)| (car.gear, label.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec![(4, "fast")]);
+        assert_eq!(
+            universe
+                .nodes()
+                .with_class_component::<Bike, String>()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn universe_cloning() {
+        // Define a component and a class that can be cloned
+        #[derive(Clone)]
+        struct Name(&'static str);
+        define_class! {
+            #[derive(Clone)]
+            class Person {
+                name: Name,
+            }
+        }
+
+        // Create a universe with a small tree
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, Person { name: Name("root") });
+        let child_handle = universe.create_node(
+            Some(&root_handle),
+            Person {
+                name: Name("child"),
+            },
+        );
+        universe.create_node(
+            Some(&child_handle),
+            Person {
+                name: Name("grandchild"),
+            },
+        );
+
+        // Clone the universe, then modify the original
+        let clone = universe.try_clone().unwrap();
+        universe
+            .node_mut(&root_handle)
+            .unwrap()
+            .component_mut::<Name>()
+            .unwrap()
+            .0 = "changed";
+        universe.remove_node(&child_handle).unwrap();
+
+        // Assert that the clone has the same structure as the original did
+        assert_eq!(clone.node_count(), 3);
+        assert_eq!(clone.root_node_handles(), &[root_handle.clone()]);
+        assert_eq!(
+            clone.node(&root_handle).unwrap().children(),
+            &[child_handle.clone()]
+        );
+
+        // Assert that handles from the original resolve in the clone, to unmodified copies
+        let names = std::iter::once(clone.node(&root_handle).unwrap())
+            .chain(clone.descendants(&root_handle))
+            .map(|node| node.component::<Name>().unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["root", "child", "grandchild"]);
+        assert_eq!(clone.nodes_of_class::<Person>().count(), 3);
+
+        // Assert that a universe holding an uncloneable class cannot be cloned
+        define_class! {
+            class Stone {
+                name: Name,
+            }
+        }
+        let stone_handle = universe.create_node(
+            None,
+            Stone {
+                name: Name("stone"),
+            },
+        );
+        assert_eq!(universe.try_clone().err(), Some(Error::UncloneableClass));
+
+        // Assert that a universe holding a dynamic component cannot be cloned
+        universe.remove_node(&stone_handle).unwrap();
+        universe.insert_component(&root_handle, 5u32);
+        assert_eq!(
+            universe.try_clone().err(),
+            Some(Error::UncloneableComponent)
+        );
+    }
+
+    #[test]
+    fn class_component_type_ids() {
+        use std::any::TypeId;
+
+        use crate::class::Class;
+
+        // Define some components
+        struct Name;
+        struct Age;
+
+        // Define a class with the components
+        define_class! {
+            class Cat {
+                name: Name,
+                age: Age,
+            }
+        }
+
+        // Create a universe with a cat node, and give it a dynamic component
+        let mut universe = Universe::new();
+        let cat_handle = universe.create_node(
+            None,
+            Cat {
+                name: Name,
+                age: Age,
+            },
+        );
+        universe.insert_component(&cat_handle, 9u8);
+
+        // Assert that the class lists the declared fields' types
+        let cat_node = universe.node(&cat_handle).unwrap();
+        assert_eq!(
+            cat_node.class().component_type_ids(),
+            vec![TypeId::of::<Name>(), TypeId::of::<Age>()]
+        );
+        assert!(().component_type_ids().is_empty());
+
+        // Assert that the node lists its class's components followed by its dynamic components
+        assert_eq!(
+            cat_node.component_type_ids(),
+            vec![
+                TypeId::of::<Name>(),
+                TypeId::of::<Age>(),
+                TypeId::of::<u8>()
+            ]
+        );
+    }
+
+    #[test]
+    fn handle_collection() {
+        // Define some classes
+        define_class! {
+            class Dog {
+                age: u32,
+            }
+
+            class Cat {
+                lives: u8,
+            }
+        }
+
+        // Create a universe with a mixed population
+        let mut universe = Universe::new();
+        let dog_handles = universe.create_nodes(None, (0..3).map(|age| Dog { age }));
+        universe.create_nodes(None, [Cat { lives: 9 }, Cat { lives: 9 }]);
+
+        // Collect the handles of the dogs, then use them to give each dog a parent
+        let handles = universe.nodes().with_class::<Dog>().collect_handles();
+        for handle in &handles {
+            let kennel_handle = universe.create_node(None, ());
+            universe
+                .change_parent(handle, Some(&kennel_handle))
+                .unwrap();
+        }
+
+        // Assert that exactly the dogs were reparented
+        let mut handles = handles;
+        handles.sort_by_key(|handle| universe.node(handle).unwrap().id());
+        assert_eq!(handles, dog_handles);
+        assert!(dog_handles.iter().all(|handle| !universe.is_root(handle)));
+
+        // Collect the handles of the cats mutably, then remove them
+        let cat_handles = universe.nodes_mut().with_class::<Cat>().collect_handles();
+        assert_eq!(cat_handles.len(), 2);
+        for handle in &cat_handles {
+            universe.remove_node(handle).unwrap();
+        }
+        assert_eq!(universe.nodes().with_class::<Cat>().count(), 0);
+    }
+
+    #[test]
+    fn reparent_invalid_parent() {
+        // Create a universe with a node under a parent, and a removed node
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let node_handle = universe.create_node(Some(&parent_handle), ());
+        let removed_handle = universe.create_node(None, ());
+        universe.remove_node(&removed_handle).unwrap();
+
+        // Assert that reparenting the node under the removed node fails
+        assert_eq!(
+            universe.change_parent(&node_handle, Some(&removed_handle)),
+            Err(Error::InvalidParent)
+        );
+
+        // Assert that the node's original parent link is preserved
+        assert_eq!(
+            universe.node(&node_handle).unwrap().parent(),
+            Some(&parent_handle)
+        );
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().children(),
+            &[node_handle.clone()]
+        );
+        assert_eq!(universe.root_node_handles(), &[parent_handle.clone()]);
+    }
+
+    #[test]
+    fn reparent_to_same_parent() {
+        // Create a universe with a parent of three children, and some roots
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let child_handles = universe.create_nodes(Some(&parent_handle), [(), (), ()]);
+        let other_root_handle = universe.create_node(None, ());
+
+        // Reparent the first child to its current parent, and a root node to no parent
+        assert_eq!(
+            universe.change_parent(&child_handles[0], Some(&parent_handle)),
+            Ok(Some(parent_handle.clone()))
+        );
+        assert_eq!(universe.change_parent(&parent_handle, None), Ok(None));
+
+        // Assert that the order of the children and of the roots is unchanged
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().children(),
+            child_handles.as_slice()
+        );
+        assert_eq!(
+            universe.root_node_handles(),
+            &[parent_handle.clone(), other_root_handle.clone()]
+        );
+    }
+
+    #[test]
+    fn bulk_reparenting() {
+        // Create a universe with three nodes scattered under different parents
+        let mut universe = Universe::new();
+        let group_handle = universe.create_node(None, ());
+        let parent_handle1 = universe.create_node(None, ());
+        let parent_handle2 = universe.create_node(Some(&parent_handle1), ());
+        let node_handle1 = universe.create_node(Some(&parent_handle1), ());
+        let node_handle2 = universe.create_node(Some(&parent_handle2), ());
+        let node_handle3 = universe.create_node(None, ());
+
+        // Reparent the nodes under the group node
+        let node_handles = [
+            node_handle2.clone(),
+            node_handle3.clone(),
+            node_handle1.clone(),
+        ];
+        universe
+            .reparent_many(&node_handles, Some(&group_handle))
+            .unwrap();
+
+        // Assert that the group node's children follow the order they were given in
+        assert_eq!(
+            universe.node(&group_handle).unwrap().children(),
+            &node_handles
+        );
+        for node_handle in &node_handles {
+            assert_eq!(
+                universe.node(node_handle).unwrap().parent(),
+                Some(&group_handle)
+            );
+        }
+
+        // Assert that the old parents lost them
+        assert_eq!(
+            universe.node(&parent_handle1).unwrap().children(),
+            &[parent_handle2.clone()]
+        );
+        assert!(universe
+            .node(&parent_handle2)
+            .unwrap()
+            .children()
+            .is_empty());
+        assert_eq!(
+            universe.root_node_handles(),
+            &[group_handle.clone(), parent_handle1.clone()]
+        );
+
+        // Assert that moving an ancestor of the new parent is rejected, and nothing is moved
+        assert_eq!(
+            universe.reparent_many(
+                &[node_handle3.clone(), group_handle.clone()],
+                Some(&node_handle1)
+            ),
+            Err(Error::WouldCreateCycle)
+        );
+        assert_eq!(
+            universe.node(&group_handle).unwrap().children(),
+            &node_handles
+        );
+    }
+
+    #[test]
+    fn root_lookup() {
+        // Create a universe with a chain of nodes under a root, and a second root
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(Some(&root_handle), ());
+        let grandchild_handle = universe.create_node(Some(&child_handle), ());
+        let deep_handle = universe.create_node(Some(&grandchild_handle), ());
+        let other_root_handle = universe.create_node(None, ());
+
+        // Assert that every node in the chain finds the same root
+        assert_eq!(universe.root_of(&deep_handle), Some(root_handle.clone()));
+        assert_eq!(universe.root_of(&child_handle), Some(root_handle.clone()));
+        assert_eq!(universe.root_of(&root_handle), Some(root_handle.clone()));
+        assert_eq!(
+            universe.root_of(&other_root_handle),
+            Some(other_root_handle.clone())
+        );
+
+        // Assert that a removed node has no root
+        universe.remove_node(&deep_handle).unwrap();
+        assert_eq!(universe.root_of(&deep_handle), None);
+    }
+
+    #[test]
+    fn node_builder() {
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Position(i32);
+        #[derive(Debug, PartialEq)]
+        struct Health(u32);
+
+        // Define a class using one of the components
+        define_class! {
+            class Player {
+                position: Position,
+            }
+        }
+
+        // Build a node with a class and a dynamic component under a parent
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let player_handle = universe
+            .build_node(Some(&parent_handle))
+            .with_class(Player {
+                position: Position(3),
+            })
+            .with_component(Health(100))
+            .spawn();
+
+        // Assert that the node was created under the parent with both components
+        let player = universe.node(&player_handle).unwrap();
+        assert!(player.class_is::<Player>());
+        assert_eq!(player.parent(), Some(&parent_handle));
+        assert_eq!(player.component::<Position>(), Some(&Position(3)));
+        assert_eq!(player.component::<Health>(), Some(&Health(100)));
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().children(),
+            &[player_handle.clone()]
+        );
+
+        // Assert that the node is found by the class and component indexes
+        assert_eq!(
+            universe
+                .nodes_of_class::<Player>()
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            vec![player_handle.clone()]
+        );
+        assert_eq!(
+            universe
+                .nodes_with_component_indexed::<Health>()
+                .map(|(node, _health)| node.handle().clone())
+                .collect::<Vec<_>>(),
+            vec![player_handle.clone()]
+        );
+
+        // Assert that a node built without a class is a root node with the () class
+        let empty_handle = universe.build_node(None).spawn();
+        assert!(universe.node(&empty_handle).unwrap().class_is::<()>());
+        assert!(universe.is_root(&empty_handle));
+    }
+
+    #[test]
+    fn leaf_iteration() {
+        use std::collections::HashSet;
+
+        // Create a universe with two trees
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let branch_handle = universe.create_node(Some(&root_handle), ());
+        let leaf_handles = universe.create_nodes(Some(&branch_handle), [(), ()]);
+        let leaf_handle = universe.create_node(Some(&root_handle), ());
+        let lone_root_handle = universe.create_node(None, ());
+
+        // Assert that every leaf in the universe is found
+        assert_eq!(
+            universe
+                .leaves()
+                .map(|node| node.handle().clone())
+                .collect::<HashSet<_>>(),
+            HashSet::from([
+                leaf_handles[0].clone(),
+                leaf_handles[1].clone(),
+                leaf_handle.clone(),
+                lone_root_handle.clone(),
+            ])
+        );
+
+        // Assert that the leaves of each subtree are found in depth-first order
+        assert_eq!(
+            universe
+                .leaves_of(&root_handle)
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                leaf_handles[0].clone(),
+                leaf_handles[1].clone(),
+                leaf_handle.clone(),
+            ]
+        );
+        assert_eq!(
+            universe
+                .leaves_of(&branch_handle)
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            leaf_handles
+        );
+        assert_eq!(
+            universe
+                .leaves_of(&lone_root_handle)
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            vec![lone_root_handle.clone()]
+        );
+    }
+
+    #[test]
+    fn universe_diff() {
+        // Create a universe with a small tree
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let child_handles = universe.create_nodes(Some(&root_handle), [(), (), ()]);
+
+        // Assert that a clone has no differences
+        let mut clone = universe.try_clone().unwrap();
+        assert_eq!(universe.diff(&clone), UniverseDiff::default());
+
+        // Add, remove and reparent some nodes in the clone
+        let added_handle = clone.create_node(Some(&child_handles[0]), ());
+        clone.remove_node(&child_handles[1]).unwrap();
+        clone
+            .change_parent(&child_handles[2], Some(&child_handles[0]))
+            .unwrap();
+
+        // Assert that the diff captures the structural changes
+        let diff = universe.diff(&clone);
+        assert_eq!(diff.added, vec![added_handle.clone()]);
+        assert_eq!(diff.removed, vec![child_handles[1].clone()]);
+        assert_eq!(diff.reparented, vec![child_handles[2].clone()]);
+
+        // Assert that diffing the other way around swaps the added and removed nodes
+        let reverse_diff = clone.diff(&universe);
+        assert_eq!(reverse_diff.added, diff.removed);
+        assert_eq!(reverse_diff.removed, diff.added);
+        assert_eq!(reverse_diff.reparented, diff.reparented);
+    }
+
+    #[test]
+    fn class_grouping() {
+        // Define some classes
+        define_class! {
+            class Cat {
+                lives: u8,
+            }
+
+            class Dog {
+                age: u32,
+            }
+        }
+
+        // Create a universe with a mixed population of nodes
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let mut dog_handles = Vec::new();
+        for i in 0..3 {
+            universe.create_node(Some(&root_handle), Cat { lives: i });
+            dog_handles.push(universe.create_node(Some(&root_handle), Dog { age: i as u32 }));
+        }
+        universe.create_node(Some(&root_handle), Cat { lives: 9 });
+
+        // Assert that the nodes are grouped by class name
+        let groups = universe.group_by_class();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups["()"].len(), 1);
+        assert_eq!(groups["Cat"].len(), 4);
+        assert_eq!(groups["Dog"].len(), 3);
+        assert!(groups["Cat"].iter().all(|node| node.class_is::<Cat>()));
+
+        // Assert that the handle groups can be used to modify the nodes
+        let handle_groups = universe.group_by_class_handles();
+        assert_eq!(handle_groups["Dog"], dog_handles);
+        for handle in &handle_groups["Dog"] {
+            universe
+                .node_mut(handle)
+                .unwrap()
+                .class_as_mut::<Dog>()
+                .unwrap()
+                .age += 1;
+        }
+        assert_eq!(
+            universe
+                .nodes_of_class::<Dog>()
+                .map(|node| node.class_as::<Dog>().unwrap().age)
+                .sum::<u32>(),
+            6
+        );
+    }
+
+    #[test]
+    fn sibling_swapping() {
+        // Create a universe with a parent of three children, and another root
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let child_handles = universe.create_nodes(Some(&parent_handle), [(), (), ()]);
+        let other_root_handle = universe.create_node(None, ());
+
+        // Swap the first and last children and assert the resulting order
+        universe
+            .swap_siblings(&child_handles[2], &child_handles[0])
+            .unwrap();
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().children(),
+            &[
+                child_handles[2].clone(),
+                child_handles[1].clone(),
+                child_handles[0].clone(),
+            ]
+        );
+
+        // Swap the root nodes and assert the resulting order
+        universe
+            .swap_siblings(&parent_handle, &other_root_handle)
+            .unwrap();
+        assert_eq!(
+            universe.root_node_handles(),
+            &[other_root_handle.clone(), parent_handle.clone()]
+        );
+
+        // Assert that nodes with different parents cannot be swapped
+        assert_eq!(
+            universe.swap_siblings(&child_handles[1], &other_root_handle),
+            Err(Error::NotSiblings)
+        );
+        assert_eq!(
+            universe.swap_siblings(&parent_handle, &child_handles[1]),
+            Err(Error::NotSiblings)
+        );
+    }
+
+    #[test]
+    fn subtree_walking() {
+        // Create a universe with a root of two branches, each with two leaves
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let pruned_handle = universe.create_node(Some(&root_handle), ());
+        let pruned_leaf_handles = universe.create_nodes(Some(&pruned_handle), [(), ()]);
+        let kept_handle = universe.create_node(Some(&root_handle), ());
+        let kept_leaf_handles = universe.create_nodes(Some(&kept_handle), [(), ()]);
+
+        // Walk the tree, pruning the first branch
+        let mut visited = Vec::new();
+        universe.walk(&root_handle, |node| {
+            visited.push(node.handle().clone());
+            if node.handle() == &pruned_handle {
+                Walk::SkipChildren
+            } else {
+                Walk::Continue
+            }
+        });
+
+        // Assert that the pruned branch's leaves were never visited
+        assert_eq!(
+            visited,
+            vec![
+                root_handle.clone(),
+                pruned_handle.clone(),
+                kept_handle.clone(),
+                kept_leaf_handles[0].clone(),
+                kept_leaf_handles[1].clone(),
+            ]
+        );
+        assert!(pruned_leaf_handles
+            .iter()
+            .all(|handle| !visited.contains(handle)));
+
+        // Walk the tree again, stopping at the first leaf
+        let mut visited = Vec::new();
+        universe.walk(&root_handle, |node| {
+            visited.push(node.handle().clone());
+            if node.children().is_empty() {
+                Walk::Stop
+            } else {
+                Walk::Continue
+            }
+        });
+
+        // Assert that the walk ended at the first leaf
+        assert_eq!(
+            visited,
+            vec![
+                root_handle.clone(),
+                pruned_handle.clone(),
+                pruned_leaf_handles[0].clone(),
+            ]
+        );
+    }
+
+    #[test]
+    fn dynamic_component_queries() {
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Position(i32);
+        #[derive(Debug, PartialEq)]
+        struct Health(u32);
+
+        // Define a class with only one of the components
+        define_class! {
+            class Player {
+                position: Position,
+            }
+        }
+
+        // Create a universe with a player, and give it a dynamic Health component
+        let mut universe = Universe::new();
+        let player_handle = universe.create_node(
+            None,
+            Player {
+                position: Position(0),
+            },
+        );
+        universe.create_node(
+            None,
+            Player {
+                position: Position(1),
+            },
+        );
+        universe.insert_component(&player_handle, Health(100));
+
+        // Assert that queries find the dynamic component
+        assert_eq!(
+            universe
+                .nodes()
+                .with_component::<Health>()
+                .map(|(node, health)| (node.handle().clone(), health))
+                .collect::<Vec<_>>(),
+            vec![(player_handle.clone(), &Health(100))]
+        );
+        assert_eq!(universe.nodes().without_component::<Health>().count(), 1);
+        for node in universe.nodes_mut().with_component::<Health>() {
+            node.component_mut::<Health>().unwrap().0 -= 10;
+        }
+        assert_eq!(
+            universe.node(&player_handle).unwrap().component::<Health>(),
+            Some(&Health(90))
+        );
+
+        // Give the player a dynamic Position component as well
+        universe.insert_component(&player_handle, Position(5));
+
+        // Assert that the class's Position component takes precedence
+        assert_eq!(
+            universe
+                .node(&player_handle)
+                .unwrap()
+                .component::<Position>(),
+            Some(&Position(0))
+        );
+        assert_eq!(
+            universe
+                .nodes()
+                .with_component::<Position>()
+                .filter(|(node, _position)| node.handle() == &player_handle)
+                .map(|(_node, position)| position)
+                .collect::<Vec<_>>(),
+            vec![&Position(0)]
+        );
+    }
+
+    #[test]
+    fn node_component_entry() {
+        // Define some components
+        #[derive(Debug, PartialEq)]
+        struct Position(i32);
+        #[derive(Debug, PartialEq)]
+        struct Inventory(Vec<&'static str>);
+
+        // Define a class with only one of the components
+        define_class! {
+            class Player {
+                position: Position,
+            }
+        }
+
+        // Create a universe with a player
+        let mut universe = Universe::new();
+        let player_handle = universe.create_node(
+            None,
+            Player {
+                position: Position(0),
+            },
+        );
+
+        // Add items to the player's inventory, creating the inventory when it is missing
+        let mut created = 0;
+        for item in ["sword", "shield"] {
+            universe
+                .component_entry::<Inventory>(&player_handle)
+                .or_insert_with(|| {
+                    created += 1;
+                    Inventory(Vec::new())
+                })
+                .0
+                .push(item);
+        }
+
+        // Assert that the inventory was created only once, and holds both items
+        assert_eq!(created, 1);
+        assert_eq!(
+            universe.nodes_with_component_indexed::<Inventory>().count(),
+            1
+        );
+        assert_eq!(
+            universe
+                .node(&player_handle)
+                .unwrap()
+                .component::<Inventory>(),
+            Some(&Inventory(vec!["sword", "shield"]))
+        );
+
+        // Assert that the class's component is treated as present and is not replaced
+        universe
+            .component_entry::<Position>(&player_handle)
+            .or_insert(Position(5))
+            .0 += 1;
+        let player = universe.node(&player_handle).unwrap();
+        assert_eq!(player.component::<Position>(), Some(&Position(1)));
+        assert_eq!(universe.remove_component::<Position>(&player_handle), None);
+    }
+
+    #[test]
+    fn node_descendants_within() {
+        // Create a universe with a 4-deep tree, with two nodes at each level below the root
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let mut level_handles = vec![vec![root_handle.clone()]];
+        for depth in 0..3 {
+            let parent_handle = level_handles[depth][0].clone();
+            level_handles.push(universe.create_nodes(Some(&parent_handle), [(), ()]));
+        }
+
+        // Assert that a max depth of 0 yields only the root's children
+        assert_eq!(
+            universe
+                .descendants_within(&root_handle, 0)
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            level_handles[1]
+        );
+
+        // Assert that a max depth of 1 yields the children and grandchildren in depth-first order
+        assert_eq!(
+            universe
+                .descendants_within(&root_handle, 1)
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                level_handles[1][0].clone(),
+                level_handles[2][0].clone(),
+                level_handles[2][1].clone(),
+                level_handles[1][1].clone(),
+            ]
+        );
+
+        // Assert that a large enough max depth yields every descendant
+        assert_eq!(
+            universe
+                .descendants_within(&root_handle, 2)
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            universe
+                .descendants(&root_handle)
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(universe.descendants_within(&root_handle, 2).count(), 6);
+    }
+
+    #[test]
+    fn node_creation_with_mutable_reference() {
+        // Define a component and a class using it
+        #[derive(Debug, PartialEq)]
+        struct Health(u32);
+        define_class! {
+            class Player {
+                health: Health,
+            }
+        }
+
+        // Create a node and finish setting it up through the returned reference
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let (player_handle, player) = universe.create_node_mut(
+            Some(&parent_handle),
+            Player {
+                health: Health(100),
+            },
+        );
+        player.component_mut::<Health>().unwrap().0 -= 25;
+        player.__insert_component("buffed");
+        assert_eq!(player.handle(), &player_handle);
+
+        // Assert that the changes were made to the node in the universe
+        let player = universe.node(&player_handle).unwrap();
+        assert_eq!(player.component::<Health>(), Some(&Health(75)));
+        assert_eq!(player.component::<&str>(), Some(&"buffed"));
+        assert_eq!(player.parent(), Some(&parent_handle));
+    }
+
+    #[test]
+    fn node_path_between() {
+        // Create a universe with a root of two branches, and a second tree
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let branch_handles = universe.create_nodes(Some(&root_handle), [(), ()]);
+        let leaf_handle1 = universe.create_node(Some(&branch_handles[0]), ());
+        let leaf_handle2 = universe.create_node(Some(&branch_handles[1]), ());
+        let other_root_handle = universe.create_node(None, ());
+
+        // Assert that ancestor to descendant paths descend through the tree, and the reverse climbs it
+        assert_eq!(
+            universe.path_between(&root_handle, &leaf_handle1),
+            Some(vec![
+                root_handle.clone(),
+                branch_handles[0].clone(),
+                leaf_handle1.clone(),
+            ])
+        );
+        assert_eq!(
+            universe.path_between(&leaf_handle1, &root_handle),
+            Some(vec![
+                leaf_handle1.clone(),
+                branch_handles[0].clone(),
+                root_handle.clone(),
+            ])
+        );
+
+        // Assert that paths between siblings and cousins go through their common ancestor
+        assert_eq!(
+            universe.path_between(&branch_handles[0], &branch_handles[1]),
+            Some(vec![
+                branch_handles[0].clone(),
+                root_handle.clone(),
+                branch_handles[1].clone(),
+            ])
+        );
+        assert_eq!(
+            universe.path_between(&leaf_handle1, &leaf_handle2),
+            Some(vec![
+                leaf_handle1.clone(),
+                branch_handles[0].clone(),
+                root_handle.clone(),
+                branch_handles[1].clone(),
+                leaf_handle2.clone(),
+            ])
+        );
+
+        // Assert that the path from a node to itself is just the node
+        assert_eq!(
+            universe.path_between(&leaf_handle1, &leaf_handle1),
+            Some(vec![leaf_handle1.clone()])
+        );
+
+        // Assert that there is no path between nodes in different trees
+        assert_eq!(
+            universe.path_between(&leaf_handle1, &other_root_handle),
+            None
+        );
+    }
+
+    #[test]
+    fn class_field_defaults() {
+        // Define some components
+        #[derive(Debug, Default, PartialEq)]
+        struct Name(&'static str);
+        #[derive(Debug, PartialEq)]
+        struct Health(u32);
+        #[derive(Debug, PartialEq)]
+        struct Speed(u32);
+
+        // Define a class with default values for some of its fields
+        define_class! {
+            class Player {
+                name: Name,
+                health: Health = Health(100),
+                speed: Speed = Speed(5),
+            }
+        }
+
+        // Create a node with only one field overridden
+        let mut universe = Universe::new();
+        let player_handle = universe.create_node(
+            None,
+            Player {
+                speed: Speed(10),
+                ..Default::default()
+            },
+        );
+
+        // Assert that the other fields have their default values
+        let player = universe.node(&player_handle).unwrap();
+        assert_eq!(player.component::<Name>(), Some(&Name("")));
+        assert_eq!(player.component::<Health>(), Some(&Health(100)));
+        assert_eq!(player.component::<Speed>(), Some(&Speed(10)));
+    }
+
+    #[test]
+    fn component_type_id_lookup() {
+        use std::any::TypeId;
+
+        // Define some components
+        struct Position;
+        struct Velocity;
+
+        // Define some classes using the components
+        define_class! {
+            class Rock {
+                position: Position,
+            }
+
+            class Ball {
+                position: Position,
+                velocity: Velocity,
+            }
+        }
+
+        // Create a universe with a mixed population of nodes, one with a dynamic Velocity component
+        let mut universe = Universe::new();
+        for _ in 0..3 {
+            universe.create_node(None, Rock { position: Position });
+            universe.create_node(
+                None,
+                Ball {
+                    position: Position,
+                    velocity: Velocity,
+                },
+            );
+        }
+        let moving_rock_handle = universe.create_node(None, Rock { position: Position });
+        universe.insert_component(&moving_rock_handle, Velocity);
+
+        // Assert that looking up nodes by type id finds the same nodes as the generic queries
+        assert_eq!(
+            universe
+                .nodes_with_component_id(TypeId::of::<Position>())
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            universe
+                .nodes()
+                .with_component::<Position>()
+                .map(|(node, _position)| node.handle().clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            universe
+                .nodes_with_component_id(TypeId::of::<Velocity>())
+                .map(|node| node.handle().clone())
+                .collect::<Vec<_>>(),
+            universe
+                .nodes()
+                .with_component::<Velocity>()
+                .map(|(node, _velocity)| node.handle().clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            universe
+                .nodes_with_component_id(TypeId::of::<Velocity>())
+                .count(),
+            4
+        );
+        assert_eq!(
+            universe.nodes_with_component_id(TypeId::of::<u8>()).count(),
+            0
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Child is already attached to this node")]
+    fn duplicate_child_prevention() {
+        // Create a universe with a parent and a child
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(None, ());
+
+        // Attach the child to the parent twice, which panics in debug builds
+        let parent = universe.node_mut(&parent_handle).unwrap();
+        parent.__attach_child_handle(child_handle.clone());
+        parent.__attach_child_handle(child_handle);
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn duplicate_child_prevention() {
+        // Create a universe with a parent and a child
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(None, ());
+
+        // Attach the child to the parent twice
+        let parent = universe.node_mut(&parent_handle).unwrap();
+        parent.__attach_child_handle(child_handle.clone());
+        parent.__attach_child_handle(child_handle.clone());
+
+        // Assert that the child appears exactly once
+        assert_eq!(parent.children(), &[child_handle]);
+    }
+
+    #[test]
+    fn error_variants() {
+        // Create a universe with a parent and a child, and a removed node
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(None, ());
+        let child_handle = universe.create_node(Some(&parent_handle), ());
+        let removed_handle = universe.create_node(None, ());
+        universe.remove_node(&removed_handle).unwrap();
+
+        // Assert that operations on a removed node fail with InvalidHandle
+        assert_eq!(
+            universe.remove_node(&removed_handle),
+            Err(Error::InvalidHandle)
+        );
+        assert_eq!(
+            universe.change_parent(&removed_handle, None),
+            Err(Error::InvalidHandle)
+        );
+        assert_eq!(
+            universe.reparent_many(&[child_handle.clone(), removed_handle.clone()], None),
+            Err(Error::InvalidHandle)
+        );
+        assert_eq!(
+            universe.swap_siblings(&parent_handle, &removed_handle),
+            Err(Error::InvalidHandle)
+        );
+
+        // Assert that using a removed node as a parent fails with InvalidParent
+        assert_eq!(
+            universe.try_create_node(Some(&removed_handle), ()),
+            Err(Error::InvalidParent)
+        );
+        assert_eq!(
+            universe.change_parent(&child_handle, Some(&removed_handle)),
+            Err(Error::InvalidParent)
+        );
+
+        // Assert that the remaining failure modes return their own variants
+        assert_eq!(
+            universe.change_parent(&parent_handle, Some(&child_handle)),
+            Err(Error::WouldCreateCycle)
+        );
+        assert_eq!(
+            universe.swap_siblings(&parent_handle, &child_handle),
+            Err(Error::NotSiblings)
+        );
+
+        // Assert that errors can be propagated with `?`
+        use crate::handle::Handle;
+        fn move_under(
+            universe: &mut Universe,
+            parent_handle: &Handle,
+        ) -> Result<Handle, Box<dyn std::error::Error>> {
+            let node_handle = universe.try_create_node(None, ())?;
+            universe.change_parent(&node_handle, Some(parent_handle))?;
+            Ok(node_handle)
+        }
+        assert!(move_under(&mut universe, &parent_handle).is_ok());
+        assert_eq!(
+            move_under(&mut universe, &removed_handle)
+                .unwrap_err()
+                .to_string(),
+            "The parent handle does not point to a node"
+        );
+    }
+
+    #[test]
+    fn node_descendant_count() {
+        // Create a universe with a root of two branches, one with two leaves
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, ());
+        let branch_handles = universe.create_nodes(Some(&root_handle), [(), ()]);
+        let leaf_handles = universe.create_nodes(Some(&branch_handles[0]), [(), ()]);
+
+        // Assert that the counts cover every node strictly beneath each node
+        assert_eq!(universe.descendant_count(&root_handle), 4);
+        assert_eq!(universe.descendant_count(&branch_handles[0]), 2);
+        assert_eq!(universe.descendant_count(&branch_handles[1]), 0);
+        assert_eq!(universe.descendant_count(&leaf_handles[0]), 0);
+
+        // Assert that removed nodes have no descendants
+        universe.remove_subtree(&branch_handles[0]);
+        assert_eq!(universe.descendant_count(&branch_handles[0]), 0);
+        assert_eq!(universe.descendant_count(&root_handle), 1);
+    }
+
+    #[test]
+    fn parent_node_lookup() {
+        // Define a component and a class using it
+        #[derive(Debug, PartialEq)]
+        struct Name(&'static str);
+        define_class! {
+            class Person {
+                name: Name,
+            }
+        }
+
+        // Create a universe with a parent and a child
+        let mut universe = Universe::new();
+        let parent_handle = universe.create_node(
+            None,
+            Person {
+                name: Name("parent"),
+            },
+        );
+        let child_handle = universe.create_node(Some(&parent_handle), ());
+
+        // Assert that the parent's component can be read through the child
+        assert_eq!(
+            universe
+                .parent_node(&child_handle)
+                .unwrap()
+                .component::<Name>(),
+            Some(&Name("parent"))
+        );
+
+        // Modify the parent's component through the child and assert the change
+        universe
+            .parent_node_mut(&child_handle)
+            .unwrap()
+            .component_mut::<Name>()
+            .unwrap()
+            .0 = "renamed";
+        assert_eq!(
+            universe.node(&parent_handle).unwrap().component::<Name>(),
+            Some(&Name("renamed"))
+        );
+
+        // Assert that root nodes and removed nodes have no parent node
+        assert!(universe.parent_node(&parent_handle).is_none());
+        universe.remove_node(&child_handle).unwrap();
+        assert!(universe.parent_node(&child_handle).is_none());
+        assert!(universe.parent_node_mut(&child_handle).is_none());
+    }
+
+    #[test]
+    fn generic_class_component_lookup() {
+        // Define some generic classes, with and without an explicit 'static bound
+        define_class! {
+            class Wrapper<T: 'static> {
+                value: T,
+            }
+
+            class Pair<A, B> {
+                first: A,
+                second: B,
+            }
+        }
+
+        // Create a universe with nodes of different instantiations of the classes
+        let mut universe = Universe::new();
+        let number_handle = universe.create_node(None, Wrapper { value: 7u32 });
+        let text_handle = universe.create_node(None, Wrapper { value: "text" });
+        let pair_handle = universe.create_node(
+            None,
+            Pair {
+                first: 1u8,
+                second: 'b',
+            },
+        );
+
+        // Assert that components are looked up by their concrete types
+        let number = universe.node(&number_handle).unwrap();
+        assert_eq!(number.component::<u32>(), Some(&7));
+        assert_eq!(number.component::<&str>(), None);
+        assert!(number.class_is::<Wrapper<u32>>());
+        assert!(!number.class_is::<Wrapper<&str>>());
+        assert_eq!(
+            universe.node(&text_handle).unwrap().component::<&str>(),
+            Some(&"text")
+        );
+        let pair = universe.node(&pair_handle).unwrap();
+        assert_eq!(pair.component::<u8>(), Some(&1));
+        assert_eq!(pair.component::<char>(), Some(&'b'));
+
+        // Assert that queries see each instantiation separately
+        assert_eq!(universe.nodes().with_class::<Wrapper<u32>>().count(), 1);
+        assert_eq!(universe.nodes().with_component::<u32>().count(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn parallel_for_each() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        // Define a component and a class with it
+        struct Score(u64);
+        define_class! {
+            class Player {
+                score: Score,
+            }
+        }
+
+        // Create a universe with many player nodes, plus a node without a score
+        let mut universe = Universe::new();
+        for score in 0..1000 {
+            universe.create_node(
+                None,
+                Player {
+                    score: Score(score),
+                },
+            );
+        }
+        universe.create_node(None, ());
+
+        // Assert that accumulating the scores in parallel matches the serial sum
+        let serial_sum: u64 = universe
+            .nodes()
+            .with_component::<Score>()
+            .map(|(_, score)| score.0)
+            .sum();
+        let parallel_sum = AtomicU64::new(0);
+        universe.par_for_each(|node| {
+            if let Some(score) = node.component::<Score>() {
+                parallel_sum.fetch_add(score.0, Ordering::Relaxed);
+            }
+        });
+        assert_eq!(parallel_sum.into_inner(), serial_sum);
+
+        // Assert that processing a snapshot of the handles in chunks gives the same sum
+        let handles = universe.snapshot_handles();
+        assert_eq!(handles.len(), 1001);
+        let chunked_sum: u64 = handles
+            .chunks(100)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .filter_map(|handle| universe.node(handle)?.component::<Score>())
+                    .map(|score| score.0)
+                    .sum::<u64>()
+            })
+            .sum();
+        assert_eq!(chunked_sum, serial_sum);
+    }
+
+    #[test]
+    fn node_existence_checks() {
+        // Define some components
+        struct Position;
+        struct Velocity;
+        struct Health;
+
+        // Define some classes using the components
+        define_class! {
+            class Rock {
+                position: Position,
+            }
+
+            class Ball {
+                position: Position,
+                velocity: Velocity,
+            }
+        }
+
+        // Create a universe with only rocks
+        let mut universe = Universe::new();
+        for _ in 0..3 {
+            universe.create_node(None, Rock { position: Position });
+        }
+
+        // Assert that only the rocks' class and component are found
+        assert!(universe.any_node_with_class::<Rock>());
+        assert!(!universe.any_node_with_class::<Ball>());
+        assert!(universe.any_node_with_component::<Position>());
+        assert!(!universe.any_node_with_component::<Velocity>());
+        assert!(!universe.any_node_with_component::<Health>());
+
+        // Add a ball, and a dynamic Health component to one of the rocks
+        let ball_handle = universe.create_node(
+            None,
+            Ball {
+                position: Position,
+                velocity: Velocity,
+            },
+        );
+        let rock_handle = universe
+            .nodes_of_class::<Rock>()
+            .next()
+            .unwrap()
+            .handle()
+            .clone();
+        universe.insert_component(&rock_handle, Health);
+
+        // Assert that the new class and components are found
+        assert!(universe.any_node_with_class::<Ball>());
+        assert!(universe.any_node_with_component::<Velocity>());
+        assert!(universe.any_node_with_component::<Health>());
+
+        // Remove the ball and assert that its class and component are no longer found
+        universe.remove_node(&ball_handle).unwrap();
+        assert!(!universe.any_node_with_class::<Ball>());
+        assert!(!universe.any_node_with_component::<Velocity>());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn subtree_prefabs() {
+        use crate::{
+            handle::Handle,
+            serialization::{ClassRegistry, PrefabData},
+        };
+        use serde::{Deserialize, Serialize};
+
+        // Define a component and some cloneable classes using it
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        struct Brightness(u32);
+        define_class! {
+            #[derive(Clone, Serialize, Deserialize)]
+            class Lamp {
+                brightness: Brightness,
+            }
+
+            #[derive(Clone, Serialize, Deserialize)]
+            class Bulb {
+                brightness: Brightness,
+            }
+        }
+
+        // Register the classes
+        ClassRegistry::register::<Lamp>("Lamp");
+        ClassRegistry::register::<Bulb>("Bulb");
+
+        // Create a universe with a lamp of two bulbs under a room
+        let mut universe = Universe::new();
+        let room_handle = universe.create_node(None, ());
+        let lamp_handle = universe.create_node_named(
+            Some(&room_handle),
+            Lamp {
+                brightness: Brightness(10),
+            },
+            "lamp",
+        );
+        universe.create_nodes(
+            Some(&lamp_handle),
+            [
+                Bulb {
+                    brightness: Brightness(1),
+                },
+                Bulb {
+                    brightness: Brightness(2),
+                },
+            ],
+        );
+
+        // Export the lamp's subtree, sending it through JSON
+        let prefab = universe.serialize_subtree(&lamp_handle).unwrap();
+        let json = serde_json::to_string(&prefab).unwrap();
+        let prefab: PrefabData = serde_json::from_str(&json).unwrap();
+
+        // Instantiate the prefab twice, under the room and as a root node
+        let copy_handle1 = universe.instantiate_prefab(&prefab, Some(&room_handle));
+        let copy_handle2 = universe.instantiate_prefab(&prefab, None);
+
+        // Assert that each copy has the lamp's structure, names and components
+        for copy_handle in [&copy_handle1, &copy_handle2] {
+            let copy = universe.node(copy_handle).unwrap();
+            assert_eq!(copy.name_str(), Some("lamp"));
+            assert_eq!(copy.component::<Brightness>(), Some(&Brightness(10)));
+            assert_eq!(
+                universe
+                    .children_nodes(copy_handle)
+                    .map(|bulb| bulb.class_as::<Bulb>().unwrap().brightness.0)
+                    .collect::<Vec<_>>(),
+                vec![1, 2]
+            );
+        }
+        assert_eq!(
+            universe.node(&copy_handle1).unwrap().parent(),
+            Some(&room_handle)
+        );
+        assert!(universe.is_root(&copy_handle2));
+        assert_eq!(universe.node_count(), 10);
+
+        // Modify the first copy and assert that the original and the second copy are unchanged
+        for bulb in universe.nodes_mut().with_class::<Bulb>() {
+            if let Some(parent_handle) = bulb.parent() {
+                if parent_handle == &copy_handle1 {
+                    bulb.component_mut::<Brightness>().unwrap().0 += 100;
+                }
+            }
+        }
+        let brightness_sum = |universe: &Universe, handle: &Handle| {
+            universe
+                .descendants(handle)
+                .map(|bulb| bulb.component::<Brightness>().unwrap().0)
+                .sum::<u32>()
+        };
+        assert_eq!(brightness_sum(&universe, &copy_handle1), 203);
+        assert_eq!(brightness_sum(&universe, &copy_handle2), 3);
+        assert_eq!(brightness_sum(&universe, &lamp_handle), 3);
+
+        // Assert that exporting a removed node fails
+        universe.remove_subtree(&copy_handle2);
+        assert_eq!(
+            universe.serialize_subtree(&copy_handle2).unwrap_err(),
+            Error::InvalidHandle
+        );
+    }
+
+    #[test]
+    fn component_collection() {
+        // Define a class with an integer component
+        define_class! {
+            class Counter {
+                count: u32,
+            }
+        }
+
+        // Create a universe with some counters, and nodes without a count
+        let mut universe = Universe::new();
+        let mut counter_handles = Vec::new();
+        for count in 0..5 {
+            counter_handles.push(universe.create_node(None, Counter { count }));
+            universe.create_node(None, ());
+        }
+        let dynamic_handle = universe.create_node(None, ());
+        universe.insert_component(&dynamic_handle, 10u32);
+        counter_handles.push(dynamic_handle);
+
+        // Assert that the pairs cover exactly the nodes with a count
+        let counts = universe.collect_component::<u32>();
+        assert_eq!(
+            counts,
+            counter_handles
+                .iter()
+                .cloned()
+                .zip([0, 1, 2, 3, 4, 10])
+                .collect::<Vec<_>>()
+        );
+
+        // Process the counts, then write the results back by handle
+        for (handle, count) in counts {
+            *universe
+                .node_mut(&handle)
+                .unwrap()
+                .component_mut::<u32>()
+                .unwrap() = count * 2;
+        }
+        assert_eq!(
+            universe
+                .collect_component::<u32>()
+                .into_iter()
+                .map(|(_handle, count)| count)
+                .sum::<u32>(),
+            40
+        );
+    }
+
+    #[test]
+    fn component_index_after_node_removal() {
+        // Define a component
+        #[derive(Debug, PartialEq)]
+        struct Health(u32);
+
+        // Create a universe with a node holding an indexed dynamic component
+        let mut universe = Universe::new();
+        let node_handle = universe.create_node(None, ());
+        let other_handle = universe.create_node(None, ());
+        universe.insert_component(&node_handle, Health(3));
+        universe.insert_component(&other_handle, Health(5));
+
+        // Remove the component from the node directly, then remove the node
+        universe
+            .node_mut(&node_handle)
+            .unwrap()
+            .__remove_component::<Health>();
+        universe.remove_node(&node_handle).unwrap();
+
+        // Assert that the removed node is no longer found through the index
+        assert_eq!(
+            universe
+                .nodes_with_component_indexed::<Health>()
+                .map(|(node, health)| (node.handle().clone(), health))
+                .collect::<Vec<_>>(),
+            vec![(other_handle.clone(), &Health(5))]
+        );
+    }
+
+    #[test]
+    fn universe_snapshot_restore() {
+        // Define a component and a cloneable class
+        #[derive(Debug, Clone, PartialEq)]
+        struct Name(&'static str);
+        define_class! {
+            #[derive(Clone)]
+            class Person {
+                name: Name,
+            }
+        }
+
+        // Create a universe with a small tree, and take a snapshot of it
+        let mut universe = Universe::new();
+        let root_handle = universe.create_node(None, Person { name: Name("root") });
+        let child_handle = universe.create_node(
+            Some(&root_handle),
+            Person {
+                name: Name("child"),
+            },
+        );
+        let snapshot = universe.snapshot().unwrap();
+
+        // Modify, remove and add nodes after the snapshot
+        universe
+            .node_mut(&root_handle)
+            .unwrap()
+            .component_mut::<Name>()
+            .unwrap()
+            .0 = "changed";
+        universe.remove_node(&child_handle).unwrap();
+        let added_handle = universe.create_node(
+            None,
+            Person {
+                name: Name("added"),
+            },
+        );
+        assert!(universe.node(&child_handle).is_none());
+
+        // Restore the snapshot
+        universe.restore(snapshot);
+
+        // Assert that the earlier state is back, and the earlier handles resolve again
+        assert_eq!(universe.node_count(), 2);
+        assert_eq!(universe.root_node_handles(), &[root_handle.clone()]);
+        assert_eq!(
+            universe.node(&root_handle).unwrap().component::<Name>(),
+            Some(&Name("root"))
+        );
+        assert_eq!(
+            universe.node(&root_handle).unwrap().children(),
+            &[child_handle.clone()]
+        );
+        assert_eq!(
+            universe.node(&child_handle).unwrap().component::<Name>(),
+            Some(&Name("child"))
+        );
+        assert!(universe.node(&added_handle).is_none());
+    }
+
+    #[test]
+    fn universe_restore_keeps_ids_unique() {
+        // Create a universe with a node, and take a snapshot of it
+        let mut universe = Universe::new();
+        universe.create_node(None, ());
+        let snapshot = universe.snapshot().unwrap();
+
+        // Create a node after the snapshot, then restore it and create another node
+        let discarded_handle = universe.create_node(None, ());
+        let discarded_id = universe.node(&discarded_handle).unwrap().id();
+        universe.restore(snapshot);
+        let new_handle = universe.create_node(None, ());
+
+        // Assert that the new node does not reuse the id of the discarded node
+        assert_ne!(universe.node(&new_handle).unwrap().id(), discarded_id);
+        assert_eq!(universe.handle_from_id(discarded_id), None);
+
+        // Assert that a universe with dynamic components cannot be captured
+        universe.insert_component(&new_handle, 5u32);
+        assert_eq!(universe.snapshot().err(), Some(Error::UncloneableComponent));
+    }
+
+    #[test]
+    fn generic_class_cloning() {
+        use crate::class::Class;
+
+        // Define generic classes, one unmarked and the others marked to be cloned through `Clone`
+        define_class! {
+            #[derive(Clone)]
+            class Unmarked<T> {
+                value: T,
+            }
+
+            #[class(clone)]
+            #[derive(Clone)]
+            class Marked<T> {
+                value: T,
+            }
+
+            #[class(clone)]
+            #[derive(Clone)]
+            class MarkedTuple(u8, char);
+        }
+
+        // Assert that only the marked classes can be boxed as copies
+        assert!(Unmarked { value: 1u32 }.clone_box().is_none());
+        assert!(MarkedTuple(1, 'a').clone_box().is_some());
+        let copy = Marked { value: 2u32 }.clone_box().unwrap();
+        assert_eq!(
+            copy.as_any().downcast_ref::<Marked<u32>>().unwrap().value,
+            2
+        );
+
+        // Create a universe with a node of the marked class
+        let mut universe = Universe::new();
+        let handle = universe.create_node(None, Marked { value: 3u32 });
+
+        // Assert that the universe can be cloned along with the node
+        let clone = universe.try_clone().unwrap();
+        assert_eq!(clone.node(&handle).unwrap().component::<u32>(), Some(&3));
+
+        // Assert that a universe with a node of the unmarked class cannot be cloned
+        universe.create_node(None, Unmarked { value: 4u32 });
+        assert!(matches!(universe.try_clone(), Err(Error::UncloneableClass)));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_generic_class_cloning() {
+        use crate::class::Class;
+
+        // Derive generic classes, one unmarked and one marked to be cloned through `Clone`
+        #[derive(Clone, crate::Class)]
+        struct Unmarked<T> {
+            value: T,
+        }
+        #[derive(Clone, crate::Class)]
+        #[class(clone)]
+        struct Marked<T> {
+            value: T,
+        }
+
+        // Assert that only the marked class can be boxed as a copy
+        assert!(Unmarked { value: 1u32 }.clone_box().is_none());
+        let copy = Marked { value: 2u32 }.clone_box().unwrap();
+        assert_eq!(
+            copy.component(std::any::TypeId::of::<u32>())
+                .unwrap()
+                .downcast_ref::<u32>(),
+            Some(&2)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_mismatched_registration() {
+        use crate::serialization::ClassRegistry;
+        use serde::{Deserialize, Serialize};
+
+        // Define two classes, and register one of them under the other's name
+        define_class! {
+            #[derive(Serialize, Deserialize)]
+            class Impostor {
+                value: u32,
+            }
+
+            #[derive(Serialize, Deserialize)]
+            class Victim {
+                value: u64,
+            }
+        }
+        ClassRegistry::register::<Impostor>("Victim");
+
+        // Create a universe with a node of the class missing from the registry
+        let mut universe = Universe::new();
+        universe.create_node(None, Victim { value: 1 });
+
+        // Assert that serializing the universe fails instead of panicking
+        let error = serde_json::to_string(&universe).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("does not match the class registered under its name"));
+    }
 }
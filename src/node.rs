@@ -1,23 +1,39 @@
-use std::{any::TypeId, fmt::Debug};
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+};
 
 use ggutil::prelude::*;
 
-use crate::class::Class;
+use crate::{class::Class, error::Error, universe::TypeIndex};
+
+type DynamicComponent = dyn Any + Send + Sync;
 
 pub struct Node {
     handle: Option<Handle>,
+    creation_index: u64,
+    name: Option<String>,
     parent_handle: Option<Handle>,
     children_handles: Vec<Handle>,
     class: Box<dyn Class>,
+    dynamic_components: HashMap<TypeId, Box<DynamicComponent>>,
+    changed_components: HashSet<TypeId>,
+    indexed_type_ids: Vec<TypeId>,
 }
 
 impl Node {
-    pub(crate) fn __new<C: Class + 'static>(parent_handle: Option<&Handle>, class: C) -> Self {
+    pub(crate) fn __new(class: Box<dyn Class>) -> Self {
         Self {
             handle: None,
-            parent_handle: parent_handle.cloned(),
+            creation_index: 0,
+            name: None,
+            parent_handle: None,
             children_handles: Vec::new(),
-            class: Box::new(class),
+            class,
+            dynamic_components: HashMap::new(),
+            changed_components: HashSet::new(),
+            indexed_type_ids: Vec::new(),
         }
     }
 
@@ -25,16 +41,98 @@ impl Node {
         self.handle = Some(handle);
     }
 
+    pub(crate) fn __set_creation_index(&mut self, creation_index: u64) {
+        self.creation_index = creation_index;
+    }
+
+    pub(crate) fn __set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
     pub(crate) fn __push_child_handle(&mut self, handle: Handle) {
         self.children_handles.push(handle);
     }
 
+    pub(crate) fn __attach_child_handle(&mut self, handle: Handle) {
+        // A child listed twice would be visited twice by every traversal, so attaching an existing child does nothing
+        let attached = self.children_handles.contains(&handle);
+        debug_assert!(!attached, "Child is already attached to this node");
+        if !attached {
+            self.children_handles.push(handle);
+        }
+    }
+
+    pub(crate) fn __clear_child_handles(&mut self) {
+        self.children_handles.clear();
+    }
+
     pub(crate) fn __remove_child_handle(&mut self, handle: &Handle) {
         if let Some(index) = self.children_handles.iter().position(|h| h == handle) {
             self.children_handles.remove(index);
         }
     }
 
+    pub(crate) fn __move_child_handle(&mut self, handle: &Handle, index: usize) -> Option<()> {
+        let old_index = self.children_handles.iter().position(|h| h == handle)?;
+        let handle = self.children_handles.remove(old_index);
+        let index = index.min(self.children_handles.len());
+        self.children_handles.insert(index, handle);
+        Some(())
+    }
+
+    pub(crate) fn __swap_child_handles(&mut self, a: &Handle, b: &Handle) -> Option<()> {
+        let a_index = self.children_handles.iter().position(|h| h == a)?;
+        let b_index = self.children_handles.iter().position(|h| h == b)?;
+        self.children_handles.swap(a_index, b_index);
+        Some(())
+    }
+
+    pub(crate) fn __set_class(&mut self, class: Box<dyn Class>) {
+        self.class = class;
+    }
+
+    pub(crate) fn __class_type_id(&self) -> TypeId {
+        (*self.class.as_any()).type_id()
+    }
+
+    pub(crate) fn __indexed_type_ids(&self) -> &[TypeId] {
+        &self.indexed_type_ids
+    }
+
+    pub(crate) fn __set_indexed_type_ids(&mut self, type_ids: Vec<TypeId>) {
+        self.indexed_type_ids = type_ids;
+    }
+
+    pub(crate) fn __push_indexed_type_id(&mut self, type_id: TypeId) {
+        self.indexed_type_ids.push(type_id);
+    }
+
+    pub(crate) fn __remove_indexed_type_id(&mut self, type_id: TypeId) {
+        self.indexed_type_ids.retain(|indexed| *indexed != type_id);
+    }
+
+    // Dynamic components are type-erased, so a node can only be cloned if it has none
+    pub(crate) fn __try_clone(&self) -> Result<Node, Error> {
+        if !self.dynamic_components.is_empty() {
+            return Err(Error::UncloneableComponent);
+        }
+        Ok(Self {
+            handle: self.handle.clone(),
+            creation_index: self.creation_index,
+            name: self.name.clone(),
+            parent_handle: self.parent_handle.clone(),
+            children_handles: self.children_handles.clone(),
+            class: self.class.clone_box().ok_or(Error::UncloneableClass)?,
+            dynamic_components: HashMap::new(),
+            changed_components: self.changed_components.clone(),
+            indexed_type_ids: self.indexed_type_ids.clone(),
+        })
+    }
+
+    pub(crate) fn __clear_changed(&mut self) {
+        self.changed_components.clear();
+    }
+
     pub(crate) fn __set_parent_handle(&mut self, handle: Option<&Handle>) {
         self.parent_handle = handle.cloned();
     }
@@ -49,23 +147,137 @@ impl Node {
         &self.children_handles
     }
 
+    /// Returns the number of children of this node.
+    pub fn child_count(&self) -> usize {
+        self.children_handles.len()
+    }
+
     /// Returns the node's unique handle
     pub fn handle(&self) -> &Handle {
         self.handle.as_ref().expect("Handle not set!")
     }
 
+    /// Returns a numeric id for this node, which can be stored or sent outside of the universe.
+    /// Ids are unique among the nodes created in this universe, and are never reused, even after the node is removed.
+    /// Ids increase in the order nodes are inserted into the universe.
+    /// A node is given a new id when it is moved to another universe (by `Universe::merge_from` or `Universe::transfer_subtree`) or deserialized.
+    /// Ids are not unique across universes: a universe and its copy from `Universe::try_clone` hand out the same ids after they diverge.
+    pub fn id(&self) -> u64 {
+        self.creation_index
+    }
+
+    /// Returns the node's name, if it was given one.
+    pub fn name_str(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     /// Returns the component of type T belonging to this node, if it has one.
+    /// The node's class is checked first, followed by the node's dynamic components.
+    /// If both define a component of type T, the class's component is returned.
     pub fn component<T: 'static>(&self) -> Option<&T> {
+        let type_id = TypeId::of::<T>();
         self.class
-            .component(TypeId::of::<T>())
+            .component(type_id)
+            .or_else(|| {
+                self.dynamic_components
+                    .get(&type_id)
+                    .map(|cmp| &**cmp as &dyn Any)
+            })
             .map(|cmp| cmp.downcast_ref::<T>().unwrap())
     }
 
     /// Returns the component of type T belonging to this node, if it has one.
+    /// The node's class is checked first, followed by the node's dynamic components.
+    /// If both define a component of type T, the class's component is returned.
+    /// The component is marked as changed until `Universe::clear_change_ticks` is called.
     pub fn component_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        let type_id = TypeId::of::<T>();
+        let component = match self.class.component_mut(type_id) {
+            Some(cmp) => Some(cmp),
+            None => self
+                .dynamic_components
+                .get_mut(&type_id)
+                .map(|cmp| &mut **cmp as &mut dyn Any),
+        }?;
+        self.changed_components.insert(type_id);
+        Some(component.downcast_mut::<T>().unwrap())
+    }
+
+    /// Returns whether this node has a component of type T, either from its class or as a dynamic component.
+    pub fn has_component<T: 'static>(&self) -> bool {
+        self.has_component_id(TypeId::of::<T>())
+    }
+
+    /// Returns whether this node has a component with the given type id, either from its class or as a dynamic component.
+    pub fn has_component_id(&self, type_id: TypeId) -> bool {
+        self.class.component(type_id).is_some() || self.dynamic_components.contains_key(&type_id)
+    }
+
+    /// Returns the component of type T belonging to this node.
+    /// Panics with the node's class name and the component's type name if the node does not have one.
+    pub fn expect_component<T: 'static>(&self) -> &T {
+        let class_name = self.class.name();
+        self.component::<T>()
+            .unwrap_or_else(|| panic_missing_component::<T>(class_name))
+    }
+
+    /// Returns the component of type T belonging to this node.
+    /// Panics with the node's class name and the component's type name if the node does not have one.
+    pub fn expect_component_mut<T: 'static>(&mut self) -> &mut T {
+        let class_name = self.class.name();
+        self.component_mut::<T>()
+            .unwrap_or_else(|| panic_missing_component::<T>(class_name))
+    }
+
+    /// Returns whether the component of type T has been borrowed mutably through `component_mut`
+    /// since the last call to `Universe::clear_change_ticks`.
+    pub fn is_changed<T: 'static>(&self) -> bool {
+        self.changed_components.contains(&TypeId::of::<T>())
+    }
+
+    /// Returns the field names and type ids of the components defined by this node's class.
+    /// Dynamic components are not included.
+    pub fn component_types(&self) -> impl Iterator<Item = (&'static str, TypeId)> + '_ {
         self.class
-            .component_mut(TypeId::of::<T>())
-            .map(|cmp| cmp.downcast_mut::<T>().unwrap())
+            .components_iter()
+            .map(|(name, type_id, _component)| (name, type_id))
+    }
+
+    /// Returns the type ids of every component of this node, those defined by its class followed by its dynamic components.
+    pub fn component_type_ids(&self) -> Vec<TypeId> {
+        let mut type_ids = self.class.component_type_ids();
+        for type_id in self.dynamic_components.keys() {
+            if !type_ids.contains(type_id) {
+                type_ids.push(*type_id);
+            }
+        }
+        type_ids
+    }
+
+    pub(crate) fn __insert_component<T: Send + Sync + 'static>(
+        &mut self,
+        component: T,
+    ) -> Option<T> {
+        self.dynamic_components
+            .insert(TypeId::of::<T>(), Box::new(component))
+            .map(|old| *old.downcast::<T>().unwrap())
+    }
+
+    pub(crate) fn __component_entry<'a, T: Send + Sync + 'static>(
+        &'a mut self,
+        component_index: &'a mut TypeIndex,
+    ) -> ComponentEntry<'a, T> {
+        ComponentEntry {
+            node: self,
+            component_index,
+            __marker: std::marker::PhantomData,
+        }
+    }
+
+    pub(crate) fn __remove_component<T: 'static>(&mut self) -> Option<T> {
+        self.dynamic_components
+            .remove(&TypeId::of::<T>())
+            .map(|old| *old.downcast::<T>().unwrap())
     }
 
     /// Returns the class object of this node.
@@ -87,12 +299,73 @@ impl Node {
     pub fn class_is<T: Class>(&self) -> bool {
         self.class.as_any().is::<T>()
     }
+
+    /// Returns whether the node has the given type of class. Equivalent to `class_is`.
+    pub fn has_class<T: Class>(&self) -> bool {
+        self.class_is::<T>()
+    }
+}
+
+/// An entry for a component of a node, returned by `Universe::component_entry`.
+pub struct ComponentEntry<'a, T> {
+    node: &'a mut Node,
+    component_index: &'a mut TypeIndex,
+    __marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Send + Sync + 'static> ComponentEntry<'a, T> {
+    /// Returns the component, first inserting the given value as a dynamic component if the node has no component of type T.
+    /// The component is marked as changed until `Universe::clear_change_ticks` is called.
+    pub fn or_insert(self, component: T) -> &'a mut T {
+        self.or_insert_with(|| component)
+    }
+
+    /// Returns the component, first inserting the result of the given function as a dynamic component if the node has no component of type T.
+    /// The function is only called if the component is missing.
+    /// The component is marked as changed until `Universe::clear_change_ticks` is called.
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'a mut T {
+        let node = self.node;
+        let type_id = TypeId::of::<T>();
+        node.changed_components.insert(type_id);
+        if node.class.component(type_id).is_some() {
+            return node
+                .class
+                .component_mut(type_id)
+                .unwrap()
+                .downcast_mut::<T>()
+                .unwrap();
+        }
+        if !node.dynamic_components.contains_key(&type_id) {
+            // The component is new to the node, so the node is added to the component index
+            self.component_index
+                .entry(type_id)
+                .or_default()
+                .insert(node.id(), node.handle().clone());
+            node.indexed_type_ids.push(type_id);
+        }
+        let component = node
+            .dynamic_components
+            .entry(type_id)
+            .or_insert_with(|| Box::new(f()));
+        (&mut **component as &mut dyn Any)
+            .downcast_mut::<T>()
+            .unwrap()
+    }
+}
+
+fn panic_missing_component<T>(class_name: &str) -> ! {
+    panic!(
+        "Node of class {} has no component of type {}",
+        class_name,
+        std::any::type_name::<T>()
+    )
 }
 
 impl Debug for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Node")
             .field("handle", &self.handle)
+            .field("name", &self.name)
             .field("parent_handle", &self.parent_handle)
             .field("children_handles", &self.children_handles)
             .field("class", &self.class.name())
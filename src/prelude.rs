@@ -0,0 +1,35 @@
+//! Re-exports the types and traits needed to build and query a universe.
+//!
+//! ```
+//! use multiverse_ecs::prelude::*;
+//!
+//! define_class! {
+//!     class Dog {
+//!         age: u32,
+//!     }
+//! }
+//!
+//! let mut universe = Universe::new();
+//! let root_handle: Handle = universe.create_node(None, ());
+//! universe.create_node(Some(&root_handle), Dog { age: 3 });
+//!
+//! let ages = universe
+//!     .nodes()
+//!     .with_component::<u32>()
+//!     .map(|(_, age)| *age)
+//!     .collect::<Vec<_>>();
+//! assert_eq!(ages, vec![3]);
+//! ```
+
+pub use crate::{
+    class::{Class, ClassDynComponent},
+    define_class,
+    error::Error,
+    handle::{Handle, TypedHandle},
+    node::Node,
+    system::{Query, Schedule, System},
+    universe::{ComponentTuple, NodesIter, NodesIterMut, Universe, Walk},
+};
+
+#[cfg(feature = "derive")]
+pub use multiverse_ecs_derive::Class;
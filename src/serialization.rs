@@ -0,0 +1,354 @@
+use std::{collections::HashMap, fmt, sync::RwLock};
+
+use ggutil::prelude::*;
+use lazy_static::lazy_static;
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeStruct},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{class::Class, error::Error, universe::Universe};
+
+lazy_static! {
+    static ref CLASS_REGISTRY: RwLock<HashMap<&'static str, ClassRegistration>> =
+        RwLock::new(HashMap::new());
+}
+
+/// The registry of classes that can be serialized and deserialized as part of a universe.
+/// Every class present in a universe must be registered before the universe is serialized or deserialized.
+pub struct ClassRegistry;
+
+impl ClassRegistry {
+    /// Registers a class so that nodes of that class can be serialized and deserialized.
+    /// `name` must match the name returned by the class's `Class::name()`.
+    /// Only the class of each node is serialized; handles and relationships between nodes are not round-tripped.
+    pub fn register<C: Class + Serialize + DeserializeOwned>(name: &'static str) {
+        CLASS_REGISTRY.write().unwrap().insert(
+            name,
+            ClassRegistration {
+                serialize: serialize_class::<C>,
+                deserialize: deserialize_class::<C>,
+            },
+        );
+    }
+
+    /// Returns whether a class with the given name has been registered.
+    pub fn is_registered(name: &str) -> bool {
+        CLASS_REGISTRY.read().unwrap().contains_key(name)
+    }
+
+    fn registration(name: &str) -> Option<ClassRegistration> {
+        CLASS_REGISTRY.read().unwrap().get(name).copied()
+    }
+}
+
+type SerializeClassFn = fn(&dyn Class) -> Option<&dyn erased_serde::Serialize>;
+type DeserializeClassFn = for<'de> fn(
+    &mut dyn erased_serde::Deserializer<'de>,
+) -> Result<Box<dyn Class>, erased_serde::Error>;
+
+#[derive(Clone, Copy)]
+struct ClassRegistration {
+    serialize: SerializeClassFn,
+    deserialize: DeserializeClassFn,
+}
+
+// Returns None if the class is not the type registered under its name
+fn serialize_class<C: Class + Serialize>(
+    class: &dyn Class,
+) -> Option<&dyn erased_serde::Serialize> {
+    class
+        .as_any()
+        .downcast_ref::<C>()
+        .map(|class| class as &dyn erased_serde::Serialize)
+}
+
+fn deserialize_class<C: Class + DeserializeOwned>(
+    deserializer: &mut dyn erased_serde::Deserializer<'_>,
+) -> Result<Box<dyn Class>, erased_serde::Error> {
+    Ok(Box::new(erased_serde::deserialize::<C>(deserializer)?))
+}
+
+/// Universes are serialized as a flat list of nodes in depth-first order, where each node refers to its parent by index.
+/// Handles are not round-tripped; deserializing a universe creates new handles (and node ids) for all of its nodes.
+/// Dynamic components and relationships between nodes are not serialized.
+impl Serialize for Universe {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut indices = HashMap::new();
+        let mut nodes = Vec::new();
+        for root_handle in self.root_node_handles() {
+            let root = self.node(root_handle).unwrap();
+            for node in std::iter::once(root).chain(self.descendants(root_handle)) {
+                indices.insert(node.handle().clone(), nodes.len());
+                nodes.push(SerializedNode {
+                    parent: node.parent().map(|parent_handle| indices[parent_handle]),
+                    name: node.name_str(),
+                    class: SerializedClass(node.class()),
+                });
+            }
+        }
+        SerializedUniverse { nodes }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Universe {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let deserialized = DeserializedUniverse::deserialize(deserializer)?;
+        let mut universe = Universe::new();
+        let mut handles: Vec<Handle> = Vec::with_capacity(deserialized.nodes.len());
+        for node in deserialized.nodes {
+            let parent_handle = match node.parent {
+                Some(index) => Some(handles.get(index).cloned().ok_or_else(|| {
+                    de::Error::custom(format!("Node refers to an invalid parent index {}", index))
+                })?),
+                None => None,
+            };
+            let handle = universe.__create_node_boxed(parent_handle.as_ref(), node.class.0);
+            universe.node_mut(&handle).unwrap().__set_name(node.name);
+            handles.push(handle);
+        }
+        Ok(universe)
+    }
+}
+
+/// A copy of a subtree of a universe, which can be instantiated any number of times. Returned by `Universe::serialize_subtree`.
+///
+/// Prefabs are serialized in the same format as universes, so every class in a prefab must be registered with `ClassRegistry`.
+/// Every class in a prefab must also be cloneable (see `Class::clone_box`), so that it can be instantiated more than once.
+/// Dynamic components and relationships between nodes are not included.
+pub struct PrefabData {
+    nodes: Vec<PrefabNode>,
+}
+
+// A node in a prefab, in depth-first order, which refers to its parent by index
+struct PrefabNode {
+    parent: Option<usize>,
+    name: Option<String>,
+    class: Box<dyn Class>,
+}
+
+impl Universe {
+    /// Copies a node and its descendants into a prefab, which can be serialized or instantiated with `instantiate_prefab`.
+    /// Fails if the handle does not point to a node, or if the class of any node in the subtree cannot be cloned.
+    pub fn serialize_subtree(&self, root_handle: &Handle) -> Result<PrefabData, Error> {
+        let root = self.node(root_handle).ok_or(Error::InvalidHandle)?;
+        let mut indices = HashMap::new();
+        let mut nodes = Vec::new();
+        for node in std::iter::once(root).chain(self.descendants(root_handle)) {
+            let parent = if node.handle() == root_handle {
+                None
+            } else {
+                node.parent().map(|parent_handle| indices[parent_handle])
+            };
+            indices.insert(node.handle().clone(), nodes.len());
+            nodes.push(PrefabNode {
+                parent,
+                name: node.name_str().map(str::to_string),
+                class: node.class().clone_box().ok_or(Error::UncloneableClass)?,
+            });
+        }
+        Ok(PrefabData { nodes })
+    }
+
+    /// Creates a copy of a prefab's nodes in the universe under the given parent, or as a root node if the parent is None.
+    /// The copies are given new handles. Returns the handle of the copy of the prefab's root node.
+    pub fn instantiate_prefab(
+        &mut self,
+        data: &PrefabData,
+        parent_handle: Option<&Handle>,
+    ) -> Handle {
+        if let Some(parent_handle) = parent_handle {
+            assert!(
+                self.contains_node(parent_handle),
+                "No node pointed to by this handle to create the node under"
+            );
+        }
+        let mut handles: Vec<Handle> = Vec::with_capacity(data.nodes.len());
+        for node in &data.nodes {
+            let node_parent_handle = match node.parent {
+                Some(index) => Some(&handles[index]),
+                None => parent_handle,
+            };
+            // Prefab classes are checked to be cloneable when the prefab is created or deserialized
+            let class = node.class.clone_box().unwrap();
+            let handle = self.__create_node_boxed(node_parent_handle, class);
+            self.node_mut(&handle)
+                .unwrap()
+                .__set_name(node.name.clone());
+            handles.push(handle);
+        }
+        handles[0].clone()
+    }
+}
+
+impl fmt::Debug for PrefabData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrefabData")
+            .field("node_count", &self.nodes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Serialize for PrefabData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| SerializedNode {
+                parent: node.parent,
+                name: node.name.as_deref(),
+                class: SerializedClass(&*node.class),
+            })
+            .collect();
+        SerializedUniverse { nodes }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PrefabData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let deserialized = DeserializedUniverse::deserialize(deserializer)?;
+        if deserialized.nodes.is_empty() {
+            return Err(de::Error::custom("Prefab has no nodes"));
+        }
+        let mut nodes = Vec::with_capacity(deserialized.nodes.len());
+        for (index, node) in deserialized.nodes.into_iter().enumerate() {
+            // The first node is the prefab's root, and every other node must come after its parent
+            match node.parent {
+                None if index == 0 => {}
+                Some(parent) if parent < index => {}
+                _ => {
+                    return Err(de::Error::custom(
+                        "Prefab nodes must form a single tree in depth-first order",
+                    ))
+                }
+            }
+            if node.class.0.clone_box().is_none() {
+                return Err(de::Error::custom(format!(
+                    "Class {} cannot be cloned, so it cannot be part of a prefab",
+                    node.class.0.name()
+                )));
+            }
+            nodes.push(PrefabNode {
+                parent: node.parent,
+                name: node.name,
+                class: node.class.0,
+            });
+        }
+        Ok(PrefabData { nodes })
+    }
+}
+
+#[derive(Serialize)]
+struct SerializedUniverse<'a> {
+    nodes: Vec<SerializedNode<'a>>,
+}
+
+#[derive(Serialize)]
+struct SerializedNode<'a> {
+    parent: Option<usize>,
+    name: Option<&'a str>,
+    class: SerializedClass<'a>,
+}
+
+struct SerializedClass<'a>(&'a dyn Class);
+
+impl<'a> Serialize for SerializedClass<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = self.0.name();
+        let registration = ClassRegistry::registration(name)
+            .ok_or_else(|| ser::Error::custom(format!("Class {} has not been registered", name)))?;
+        let data = (registration.serialize)(self.0).ok_or_else(|| {
+            ser::Error::custom(format!(
+                "Class {} does not match the class registered under its name",
+                name
+            ))
+        })?;
+        let mut state = serializer.serialize_struct("Class", 2)?;
+        state.serialize_field("name", name)?;
+        state.serialize_field("data", data)?;
+        state.end()
+    }
+}
+
+#[derive(Deserialize)]
+struct DeserializedUniverse {
+    nodes: Vec<DeserializedNode>,
+}
+
+#[derive(Deserialize)]
+struct DeserializedNode {
+    parent: Option<usize>,
+    #[serde(default)]
+    name: Option<String>,
+    class: DeserializedClass,
+}
+
+struct DeserializedClass(Box<dyn Class>);
+
+const CLASS_FIELDS: &[&str] = &["name", "data"];
+
+impl<'de> Deserialize<'de> for DeserializedClass {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("Class", CLASS_FIELDS, ClassVisitor)
+    }
+}
+
+struct ClassVisitor;
+
+impl ClassVisitor {
+    fn registration<E: de::Error>(name: &str) -> Result<ClassRegistration, E> {
+        ClassRegistry::registration(name)
+            .ok_or_else(|| E::custom(format!("Class {} has not been registered", name)))
+    }
+}
+
+impl<'de> Visitor<'de> for ClassVisitor {
+    type Value = DeserializedClass;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a class name followed by the class data")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let name: String = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let registration = Self::registration(&name)?;
+        let class = seq
+            .next_element_seed(ClassSeed(registration))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(DeserializedClass(class))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut registration = None;
+        let mut class = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "name" => registration = Some(Self::registration(&map.next_value::<String>()?)?),
+                "data" => {
+                    let registration = registration.ok_or_else(|| {
+                        de::Error::custom("Class data must come after the class name")
+                    })?;
+                    class = Some(map.next_value_seed(ClassSeed(registration))?);
+                }
+                _ => return Err(de::Error::unknown_field(&key, CLASS_FIELDS)),
+            }
+        }
+        class
+            .map(DeserializedClass)
+            .ok_or_else(|| de::Error::missing_field("data"))
+    }
+}
+
+struct ClassSeed(ClassRegistration);
+
+impl<'de> DeserializeSeed<'de> for ClassSeed {
+    type Value = Box<dyn Class>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        (self.0.deserialize)(&mut deserializer).map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::universe::{NodeValuesMut, NodesIterMut, NodesWithComponentMut, Universe};
+
+/// A query over the nodes of a universe which have a component of type C, yielding each of the components mutably.
+pub struct Query<'a, C: 'static> {
+    iter: NodesWithComponentMut<'a, NodeValuesMut<'a>, C>,
+}
+
+impl<'a, C: 'static> Iterator for Query<'a, C> {
+    type Item = &'a mut C;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next()
+            .map(|node| node.component_mut::<C>().unwrap())
+    }
+}
+
+/// A system which can be run over a universe, usually once per frame.
+pub trait System {
+    fn run(&mut self, universe: &mut Universe);
+}
+
+impl<F: FnMut(&mut Universe)> System for F {
+    fn run(&mut self, universe: &mut Universe) {
+        self(universe)
+    }
+}
+
+/// A list of systems which are run in sequence, in the order they were added.
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<Box<dyn System>>,
+}
+
+impl Schedule {
+    /// Creates a new schedule with no systems.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a system which is given the whole universe.
+    pub fn add_system(&mut self, system: impl System + 'static) -> &mut Self {
+        self.systems.push(Box::new(system));
+        self
+    }
+
+    /// Adds a system which is given a query over the nodes with a component of type C.
+    pub fn add_query_system<C: 'static>(
+        &mut self,
+        mut system: impl FnMut(Query<'_, C>) + 'static,
+    ) -> &mut Self {
+        self.add_system(move |universe: &mut Universe| universe.run_system(&mut system))
+    }
+
+    /// Runs each of the systems over the universe, in the order they were added.
+    pub fn run(&mut self, universe: &mut Universe) {
+        for system in &mut self.systems {
+            system.run(universe);
+        }
+    }
+}
+
+impl Universe {
+    /// Runs a system over a query of the nodes with a component of type C.
+    pub fn run_system<C: 'static>(&mut self, mut system: impl FnMut(Query<'_, C>)) {
+        system(Query {
+            iter: self.nodes_mut().with_component::<C>(),
+        })
+    }
+}
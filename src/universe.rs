@@ -1,15 +1,56 @@
-use std::marker::PhantomData;
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt::{Debug, Write},
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
 
 use ggutil::prelude::*;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
-use crate::{class::Class, node::Node};
+use crate::{
+    class::Class,
+    error::Error,
+    handle::TypedHandle,
+    node::{ComponentEntry, Node},
+};
 
 /// A universe which contains any number of nodes.
-#[derive(Debug)]
 pub struct Universe {
     /// The nodes in the universe
-    nodes: HandleMap<Node>,
+    nodes: HandleMap<StoredNode>,
     roots: Vec<Handle>,
+    /// The creation index to give the next node inserted into the universe
+    next_creation_index: u64,
+    /// The handle of each node, keyed by the node's id, which keeps them in creation order
+    id_index: BTreeMap<u64, Handle>,
+    /// The handles of the nodes of each class, keyed by the class's type id
+    class_index: TypeIndex,
+    /// The handles of the nodes exposing each component, keyed by the component's type id
+    component_index: TypeIndex,
+    /// The relationships between nodes, keyed by relationship type
+    relationships: HashMap<TypeId, Relationship>,
+    /// The callbacks to call when nodes are created, removed or reparented
+    observers: Observers,
+}
+
+/// A callback which is called with the handle of a node when something happens to it.
+pub type Observer = Box<dyn FnMut(&Handle)>;
+
+#[derive(Default)]
+struct Observers {
+    created: Vec<Observer>,
+    removed: Vec<Observer>,
+    reparented: Vec<Observer>,
+}
+
+// Calls each of the observers with the given handle
+fn notify(observers: &mut [Observer], handle: &Handle) {
+    for observer in observers {
+        observer(handle);
+    }
 }
 
 impl Universe {
@@ -17,22 +58,208 @@ impl Universe {
     pub fn new() -> Self {
         let nodes = HandleMap::new();
         let roots = Vec::new();
+        let next_creation_index = 0;
+        let id_index = BTreeMap::new();
+        let class_index = HashMap::new();
+        let component_index = HashMap::new();
+        let relationships = HashMap::new();
+        let observers = Observers::default();
+
+        Universe {
+            nodes,
+            roots,
+            next_creation_index,
+            id_index,
+            class_index,
+            component_index,
+            relationships,
+            observers,
+        }
+    }
+
+    /// Registers a callback to be called with the handle of every node created in the universe.
+    pub fn on_node_created(&mut self, observer: impl FnMut(&Handle) + 'static) {
+        self.observers.created.push(Box::new(observer));
+    }
+
+    /// Registers a callback to be called with the handle of every node removed from the universe.
+    /// The handle no longer points to a node when the callback is called.
+    pub fn on_node_removed(&mut self, observer: impl FnMut(&Handle) + 'static) {
+        self.observers.removed.push(Box::new(observer));
+    }
 
-        Universe { nodes, roots }
+    /// Registers a callback to be called with the handle of every node whose parent is changed.
+    pub fn on_reparented(&mut self, observer: impl FnMut(&Handle) + 'static) {
+        self.observers.reparented.push(Box::new(observer));
     }
 
     /// Creates a new node in the universe. Returns the node's unique Handle.
+    /// Panics if the parent handle does not point to a node; see `try_create_node`.
     pub fn create_node<C: Class + 'static>(
         &mut self,
         parent_handle: Option<&Handle>,
         class: C,
     ) -> Handle {
-        let node = Node::__new(parent_handle, class);
-        let node_handle = self.nodes.insert(node);
+        self.try_create_node(parent_handle, class)
+            .expect("No node pointed to by this handle to create the node under")
+    }
+
+    /// Creates a new node in the universe. Returns the node's unique Handle.
+    /// Fails without modifying the universe if the parent handle does not point to a node.
+    pub fn try_create_node<C: Class + 'static>(
+        &mut self,
+        parent_handle: Option<&Handle>,
+        class: C,
+    ) -> Result<Handle, Error> {
+        if let Some(parent_handle) = parent_handle {
+            if !self.contains_node(parent_handle) {
+                return Err(Error::InvalidParent);
+            }
+        }
+        Ok(self.__create_node_boxed(parent_handle, Box::new(class)))
+    }
+
+    /// Creates a new node in the universe. Returns the node's unique handle, typed with the node's class.
+    pub fn create_node_typed<C: Class + 'static>(
+        &mut self,
+        parent_handle: Option<&Handle>,
+        class: C,
+    ) -> TypedHandle<C> {
+        TypedHandle::__new(self.create_node(parent_handle, class))
+    }
+
+    /// Creates a new node in the universe. Returns the node's unique Handle, along with the node itself for finishing its setup.
+    pub fn create_node_mut<C: Class + 'static>(
+        &mut self,
+        parent_handle: Option<&Handle>,
+        class: C,
+    ) -> (Handle, &mut Node) {
+        let node_handle = self.create_node(parent_handle, class);
+        let node = self.nodes.get_mut(&node_handle).unwrap();
+        (node_handle, node)
+    }
+
+    /// Creates a new node in the universe for each of the given classes, all attached to the same parent.
+    /// Returns the nodes' unique Handles, in the same order as the classes.
+    pub fn create_nodes<C: Class + 'static, I: IntoIterator<Item = C>>(
+        &mut self,
+        parent_handle: Option<&Handle>,
+        classes: I,
+    ) -> Vec<Handle> {
+        if let Some(parent_handle) = parent_handle {
+            assert!(
+                self.contains_node(parent_handle),
+                "No node pointed to by this handle to create nodes under"
+            );
+        }
+        let node_handles = classes
+            .into_iter()
+            .map(|class| self.insert_unlinked(parent_handle, Node::__new(Box::new(class))))
+            .collect::<Vec<_>>();
+        match parent_handle {
+            Some(parent_handle) => {
+                let parent = self.nodes.get_mut(parent_handle).unwrap();
+                for node_handle in &node_handles {
+                    parent.__push_child_handle(node_handle.clone());
+                }
+            }
+            None => self.roots.extend(node_handles.iter().cloned()),
+        }
+        for node_handle in &node_handles {
+            notify(&mut self.observers.created, node_handle);
+        }
+        node_handles
+    }
+
+    /// Creates a new node in the universe at the given position among its parent's children.
+    /// Indices past the end of the parent's children place the node at the end.
+    /// Returns the node's unique Handle.
+    pub fn create_node_at<C: Class + 'static>(
+        &mut self,
+        parent_handle: &Handle,
+        index: usize,
+        class: C,
+    ) -> Handle {
+        let node_handle = self.create_node(Some(parent_handle), class);
+        self.reorder_child(parent_handle, &node_handle, index);
+        node_handle
+    }
+
+    /// Moves a node to the given position among its parent's children.
+    /// Indices past the end of the parent's children move the node to the end.
+    /// Returns None if the parent does not exist or the node is not one of its children.
+    pub fn reorder_child(
+        &mut self,
+        parent_handle: &Handle,
+        child_handle: &Handle,
+        new_index: usize,
+    ) -> Option<()> {
+        self.nodes
+            .get_mut(parent_handle)?
+            .__move_child_handle(child_handle, new_index)
+    }
+
+    /// Swaps the positions of two nodes among their parent's children, or among the root nodes if both are root nodes.
+    /// Fails without modifying the universe if either node does not exist or the nodes do not share a parent.
+    pub fn swap_siblings(&mut self, a: &Handle, b: &Handle) -> Result<(), Error> {
+        let a_parent_handle = self.node(a).ok_or(Error::InvalidHandle)?.parent().cloned();
+        let b_parent_handle = self.node(b).ok_or(Error::InvalidHandle)?.parent().cloned();
+        if a_parent_handle != b_parent_handle {
+            return Err(Error::NotSiblings);
+        }
+        match &a_parent_handle {
+            Some(parent_handle) => {
+                self.nodes
+                    .get_mut(parent_handle)
+                    .unwrap()
+                    .__swap_child_handles(a, b)
+                    .unwrap();
+            }
+            None => {
+                let a_index = self.roots.iter().position(|h| h == a).unwrap();
+                let b_index = self.roots.iter().position(|h| h == b).unwrap();
+                self.roots.swap(a_index, b_index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a new node in the universe with the given name. Returns the node's unique Handle.
+    /// Names do not need to be unique.
+    pub fn create_node_named<C: Class + 'static>(
+        &mut self,
+        parent_handle: Option<&Handle>,
+        class: C,
+        name: impl Into<String>,
+    ) -> Handle {
+        let node_handle = self.create_node(parent_handle, class);
         self.nodes
             .get_mut(&node_handle)
             .unwrap()
-            .__set_handle(node_handle.clone());
+            .__set_name(Some(name.into()));
+        node_handle
+    }
+
+    /// Starts building a new node under the given parent, or as a root node if the parent is None.
+    /// The node has the `()` class until one is given with `NodeBuilder::with_class`, and is created by `NodeBuilder::spawn`.
+    pub fn build_node(&mut self, parent_handle: Option<&Handle>) -> NodeBuilder<'_> {
+        NodeBuilder {
+            universe: self,
+            parent_handle: parent_handle.cloned(),
+            node: Node::__new(Box::new(())),
+        }
+    }
+
+    pub(crate) fn __create_node_boxed(
+        &mut self,
+        parent_handle: Option<&Handle>,
+        class: Box<dyn Class>,
+    ) -> Handle {
+        self.__insert_node(parent_handle, Node::__new(class))
+    }
+
+    pub(crate) fn __insert_node(&mut self, parent_handle: Option<&Handle>, node: Node) -> Handle {
+        let node_handle = self.insert_unlinked(parent_handle, node);
         if let Some(parent_handle) = parent_handle {
             self.nodes
                 .get_mut(parent_handle)
@@ -41,111 +268,1391 @@ impl Universe {
         } else {
             self.roots.push(node_handle.clone());
         }
-        node_handle
+        notify(&mut self.observers.created, &node_handle);
+        node_handle
+    }
+
+    // Inserts a node and indexes it, without adding it to its parent's children or to the roots
+    fn insert_unlinked(&mut self, parent_handle: Option<&Handle>, mut node: Node) -> Handle {
+        node.__set_parent_handle(parent_handle);
+        node.__clear_child_handles();
+        let id = self.next_creation_index;
+        node.__set_creation_index(id);
+        self.next_creation_index += 1;
+        let class_type_id = node.__class_type_id();
+        let component_type_ids = node.component_type_ids();
+        node.__set_indexed_type_ids(component_type_ids.clone());
+        let node_handle = self.nodes.insert(StoredNode(node));
+        self.nodes
+            .get_mut(&node_handle)
+            .unwrap()
+            .__set_handle(node_handle.clone());
+        self.id_index.insert(id, node_handle.clone());
+        self.class_index
+            .entry(class_type_id)
+            .or_default()
+            .insert(id, node_handle.clone());
+        for type_id in component_type_ids {
+            self.component_index
+                .entry(type_id)
+                .or_default()
+                .insert(id, node_handle.clone());
+        }
+        node_handle
+    }
+
+    /// Relates one node to another with a relationship of type R, in addition to the parent/child hierarchy.
+    /// R is usually a zero-sized marker type, and each type of relationship is tracked independently.
+    /// Relating the same nodes more than once has no effect.
+    pub fn relate<R: 'static>(&mut self, from: &Handle, to: &Handle) {
+        assert!(
+            self.contains_node(from) && self.contains_node(to),
+            "No node pointed to by this handle to relate"
+        );
+        self.relationships
+            .entry(TypeId::of::<R>())
+            .or_default()
+            .insert(from, to);
+    }
+
+    /// Returns the handles of the nodes a node is related to with a relationship of type R,
+    /// in the order they were related.
+    pub fn related<R: 'static>(&self, from: &Handle) -> &[Handle] {
+        self.relationships
+            .get(&TypeId::of::<R>())
+            .and_then(|relationship| relationship.to.get(from))
+            .map(|related| related.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Removes a relationship of type R from one node to another.
+    /// Returns None if the nodes were not related with a relationship of type R.
+    pub fn unrelate<R: 'static>(&mut self, from: &Handle, to: &Handle) -> Option<()> {
+        self.relationships
+            .get_mut(&TypeId::of::<R>())?
+            .remove(from, to)
+    }
+
+    /// Changes a node's parent.
+    /// Returns the node's old parent's unique Handle, if it had one.
+    /// Fails without modifying the universe if the node or the new parent does not exist,
+    /// or if the new parent is the node itself or one of its descendants.
+    /// Changing a node's parent to its current parent does nothing.
+    pub fn change_parent(
+        &mut self,
+        node_handle: &Handle,
+        new_parent_handle: Option<&Handle>,
+    ) -> Result<Option<Handle>, Error> {
+        let old_parent_handle = self
+            .node(node_handle)
+            .ok_or(Error::InvalidHandle)?
+            .parent()
+            .cloned();
+        if old_parent_handle.as_ref() == new_parent_handle {
+            // Already attached to the new parent, so leave its position among its siblings alone
+            return Ok(old_parent_handle);
+        }
+        if let Some(new_parent_handle) = new_parent_handle {
+            if !self.contains_node(new_parent_handle) {
+                return Err(Error::InvalidParent);
+            }
+        }
+        let mut ancestor_handle = new_parent_handle.cloned();
+        while let Some(handle) = ancestor_handle {
+            if &handle == node_handle {
+                return Err(Error::WouldCreateCycle);
+            }
+            ancestor_handle = self.node(&handle).and_then(|node| node.parent().cloned());
+        }
+        if let Some(old_parent_handle) = &old_parent_handle {
+            self.nodes
+                .get_mut(old_parent_handle)
+                .unwrap()
+                .__remove_child_handle(node_handle);
+        } else {
+            self.roots.retain(|root_handle| root_handle != node_handle);
+        }
+        if let Some(new_parent_handle) = new_parent_handle {
+            self.nodes
+                .get_mut(new_parent_handle)
+                .unwrap()
+                .__attach_child_handle(node_handle.clone());
+        } else {
+            self.roots.push(node_handle.clone());
+        }
+        self.nodes
+            .get_mut(node_handle)
+            .unwrap()
+            .__set_parent_handle(new_parent_handle);
+        notify(&mut self.observers.reparented, node_handle);
+        Ok(old_parent_handle)
+    }
+
+    /// Moves each of the given nodes under a single new parent, or makes them root nodes if `new_parent_handle` is None.
+    /// The nodes are appended to the new parent's children in the order they are given.
+    /// Nothing is moved if any of the nodes or the new parent does not exist, or if moving any of the nodes would create a cycle.
+    pub fn reparent_many(
+        &mut self,
+        children: &[Handle],
+        new_parent_handle: Option<&Handle>,
+    ) -> Result<(), Error> {
+        for child_handle in children {
+            if !self.contains_node(child_handle) {
+                return Err(Error::InvalidHandle);
+            }
+        }
+        if let Some(new_parent_handle) = new_parent_handle {
+            if !self.contains_node(new_parent_handle) {
+                return Err(Error::InvalidParent);
+            }
+            // A cycle is created if any of the nodes is the new parent or one of its ancestors
+            let mut ancestor_handle = Some(new_parent_handle.clone());
+            while let Some(handle) = ancestor_handle {
+                if children.contains(&handle) {
+                    return Err(Error::WouldCreateCycle);
+                }
+                ancestor_handle = self.node(&handle).and_then(|node| node.parent().cloned());
+            }
+        }
+        for child_handle in children {
+            let old_parent_handle = self.nodes.get(child_handle).unwrap().parent().cloned();
+            if let Some(old_parent_handle) = &old_parent_handle {
+                self.nodes
+                    .get_mut(old_parent_handle)
+                    .unwrap()
+                    .__remove_child_handle(child_handle);
+            } else {
+                self.roots.retain(|root_handle| root_handle != child_handle);
+            }
+            if let Some(new_parent_handle) = new_parent_handle {
+                self.nodes
+                    .get_mut(new_parent_handle)
+                    .unwrap()
+                    .__attach_child_handle(child_handle.clone());
+            } else {
+                self.roots.push(child_handle.clone());
+            }
+            self.nodes
+                .get_mut(child_handle)
+                .unwrap()
+                .__set_parent_handle(new_parent_handle);
+            notify(&mut self.observers.reparented, child_handle);
+        }
+        Ok(())
+    }
+
+    /// Detaches a node from its parent, making it a root node.
+    /// Fails if there is no node pointed to by this handle.
+    pub fn make_root(&mut self, handle: &Handle) -> Result<(), Error> {
+        self.detach(handle).map(|_| ())
+    }
+
+    /// Detaches a node from its parent, making it a root node with its own subtree intact beneath it.
+    /// Unlike `remove_node`, the node stays in the universe and keeps its children.
+    /// Returns the handle of the node's old parent, if it had one.
+    /// Fails if there is no node pointed to by this handle.
+    pub fn detach(&mut self, handle: &Handle) -> Result<Option<Handle>, Error> {
+        // Making a node a root node cannot create a cycle, so this only fails if the node does not exist
+        self.change_parent(handle, None)
+    }
+
+    /// Returns whether the node with the given handle is a root node (a node with no parent).
+    pub fn is_root(&self, handle: &Handle) -> bool {
+        matches!(self.node(handle), Some(node) if node.parent().is_none())
+    }
+
+    /// Removes a node from the universe.
+    /// The node's children are moved to the node's parent, or become root nodes if it had no parent.
+    /// Fails if there is no node pointed to by this handle.
+    pub fn remove_node(&mut self, handle: &Handle) -> Result<(), Error> {
+        let node = self.nodes.remove(handle).ok_or(Error::InvalidHandle)?.0;
+        self.unindex_node(&node);
+        let parent_handle = node.parent().cloned();
+        if let Some(parent_handle) = &parent_handle {
+            self.nodes
+                .get_mut(parent_handle)
+                .unwrap()
+                .__remove_child_handle(handle);
+        } else {
+            self.roots.retain(|root_handle| root_handle != handle);
+        }
+        for child_handle in node.children() {
+            self.nodes
+                .get_mut(child_handle)
+                .unwrap()
+                .__set_parent_handle(parent_handle.as_ref());
+            if let Some(parent_handle) = &parent_handle {
+                self.nodes
+                    .get_mut(parent_handle)
+                    .unwrap()
+                    .__attach_child_handle(child_handle.clone());
+            } else {
+                self.roots.push(child_handle.clone());
+            }
+        }
+        notify(&mut self.observers.removed, handle);
+        for child_handle in node.children() {
+            notify(&mut self.observers.reparented, child_handle);
+        }
+        Ok(())
+    }
+
+    /// Removes every node with the given type of class from the universe, and returns how many were removed.
+    /// As with `remove_node`, the children of each removed node are preserved and attached to its parent.
+    pub fn remove_nodes_with_class<C: Class>(&mut self) -> usize {
+        let handles = self
+            .nodes_of_class::<C>()
+            .map(|node| node.handle().clone())
+            .collect::<Vec<_>>();
+        for handle in &handles {
+            self.remove_node(handle).unwrap();
+        }
+        handles.len()
+    }
+
+    /// Removes every node for which the predicate returns false, like `Vec::retain`.
+    /// As with `remove_node`, the children of each removed node are preserved and attached to its parent.
+    pub fn retain(&mut self, mut f: impl FnMut(&Node) -> bool) {
+        let handles = self
+            .nodes()
+            .filter(|node| !f(node))
+            .map(|node| node.handle().clone())
+            .collect::<Vec<_>>();
+        for handle in &handles {
+            self.remove_node(handle).unwrap();
+        }
+    }
+
+    /// Removes a node and all of its descendants from the universe.
+    /// Returns the number of nodes removed.
+    pub fn remove_subtree(&mut self, root_handle: &Handle) -> usize {
+        self.take_subtree(root_handle).len()
+    }
+
+    /// Removes a node and all of its descendants from the universe and inserts them into another universe,
+    /// attached to the given parent in the other universe.
+    /// The moved nodes are given new handles in the other universe.
+    /// Relationships between nodes of the subtree are moved with them, and relationships to nodes outside of it are dropped.
+    /// Returns the new handle of the node, or None if the node or the new parent does not exist.
+    pub fn transfer_subtree(
+        &mut self,
+        root_handle: &Handle,
+        dest: &mut Universe,
+        dest_parent_handle: Option<&Handle>,
+    ) -> Option<Handle> {
+        if !self.contains_node(root_handle) {
+            return None;
+        }
+        if let Some(dest_parent_handle) = dest_parent_handle {
+            if !dest.contains_node(dest_parent_handle) {
+                return None;
+            }
+        }
+        let subtree_handles = self.subtree_handles(root_handle);
+        let relationships = self.relationships_within(&subtree_handles.iter().collect());
+        let mut new_handles = self.move_subtree(root_handle, dest, dest_parent_handle);
+        dest.insert_relationships(relationships, &new_handles);
+        new_handles.remove(root_handle)
+    }
+
+    // Moves a node and all of its descendants into another universe, without checking the handles.
+    // Returns the new handle of each moved node, keyed by its old handle.
+    fn move_subtree(
+        &mut self,
+        root_handle: &Handle,
+        dest: &mut Universe,
+        dest_parent_handle: Option<&Handle>,
+    ) -> HashMap<Handle, Handle> {
+        let mut new_handles: HashMap<Handle, Handle> = HashMap::new();
+        for node in self.take_subtree(root_handle) {
+            let handle = node.handle().clone();
+            let new_parent_handle = if &handle == root_handle {
+                dest_parent_handle.cloned()
+            } else {
+                node.parent()
+                    .map(|parent_handle| new_handles[parent_handle].clone())
+            };
+            let new_handle = dest.__insert_node(new_parent_handle.as_ref(), node);
+            new_handles.insert(handle, new_handle);
+        }
+        new_handles
+    }
+
+    // Returns every relationship edge whose nodes are both among the given handles, as its type id and the handles it is from and to
+    fn relationships_within(&self, handles: &HashSet<&Handle>) -> Vec<(TypeId, Handle, Handle)> {
+        let mut edges = Vec::new();
+        for (type_id, relationship) in &self.relationships {
+            for from in handles {
+                let related = relationship.to.get(*from).into_iter().flatten();
+                for to in related.filter(|to| handles.contains(to)) {
+                    edges.push((*type_id, (*from).clone(), to.clone()));
+                }
+            }
+        }
+        edges
+    }
+
+    // Adds relationship edges between the new nodes which replace the nodes they were from and to
+    fn insert_relationships(
+        &mut self,
+        edges: Vec<(TypeId, Handle, Handle)>,
+        new_handles: &HashMap<Handle, Handle>,
+    ) {
+        for (type_id, from, to) in edges {
+            self.relationships
+                .entry(type_id)
+                .or_default()
+                .insert(&new_handles[&from], &new_handles[&to]);
+        }
+    }
+
+    /// Moves every node from another universe into this universe, keeping their hierarchy.
+    /// The other universe's root nodes become root nodes in this universe, in the same order.
+    /// The moved nodes are given new handles in this universe, and the other universe's relationships are moved with them.
+    /// Returns the new handles of the other universe's root nodes.
+    pub fn merge_from(&mut self, mut other: Universe) -> Vec<Handle> {
+        let other_root_handles = other.root_node_handles().to_vec();
+        let relationships = other.relationships_within(&other.id_index.values().collect());
+        let mut new_handles = HashMap::new();
+        for root_handle in &other_root_handles {
+            new_handles.extend(other.move_subtree(root_handle, self, None));
+        }
+        self.insert_relationships(relationships, &new_handles);
+        other_root_handles
+            .iter()
+            .map(|root_handle| new_handles[root_handle].clone())
+            .collect()
+    }
+
+    /// Compares the structure of this universe with another, such as a later clone of it.
+    /// Nodes are matched by handle, so this is only meaningful when both universes share their handles.
+    /// Component values are not compared.
+    pub fn diff(&self, other: &Universe) -> UniverseDiff {
+        let added = other
+            .nodes_sorted()
+            .filter(|node| !self.contains_node(node.handle()))
+            .map(|node| node.handle().clone())
+            .collect();
+        let mut removed = Vec::new();
+        let mut reparented = Vec::new();
+        for node in self.nodes_sorted() {
+            match other.node(node.handle()) {
+                Some(other_node) if other_node.parent() != node.parent() => {
+                    reparented.push(node.handle().clone())
+                }
+                Some(_) => {}
+                None => removed.push(node.handle().clone()),
+            }
+        }
+        UniverseDiff {
+            added,
+            removed,
+            reparented,
+        }
+    }
+
+    /// Removes a node and all of its descendants from the universe.
+    /// Returns the removed nodes in depth-first pre-order.
+    fn take_subtree(&mut self, root_handle: &Handle) -> Vec<Node> {
+        let parent_handle = match self.node(root_handle) {
+            Some(root) => root.parent().cloned(),
+            None => return Vec::new(),
+        };
+        if let Some(parent_handle) = &parent_handle {
+            self.nodes
+                .get_mut(parent_handle)
+                .unwrap()
+                .__remove_child_handle(root_handle);
+        } else {
+            self.roots.retain(|handle| handle != root_handle);
+        }
+
+        // Collect the handles of the subtree first, then remove them
+        let nodes = self
+            .subtree_handles(root_handle)
+            .iter()
+            .map(|handle| self.nodes.remove(handle).unwrap().0)
+            .collect::<Vec<_>>();
+        for node in &nodes {
+            self.unindex_node(node);
+            notify(&mut self.observers.removed, node.handle());
+        }
+        nodes
+    }
+
+    // Removes a node which has been taken out of the universe from the indices and relationships
+    fn unindex_node(&mut self, node: &Node) {
+        self.id_index.remove(&node.id());
+        remove_from_index(&mut self.class_index, node.__class_type_id(), node.id());
+        for type_id in node.__indexed_type_ids() {
+            remove_from_index(&mut self.component_index, *type_id, node.id());
+        }
+        for relationship in self.relationships.values_mut() {
+            relationship.remove_node(node.handle());
+        }
+    }
+
+    /// Attaches a dynamic component of type T to a node, keeping the component index up to date.
+    /// Returns the node's previous dynamic component of type T, if there was one.
+    /// If the node's class also defines a component of type T, the class's component takes precedence in lookups.
+    pub fn insert_component<T: Send + Sync + 'static>(
+        &mut self,
+        handle: &Handle,
+        component: T,
+    ) -> Option<T> {
+        let node = self
+            .nodes
+            .get_mut(handle)
+            .expect("No node pointed to by this handle to insert a component into");
+        let had_component = node.component::<T>().is_some();
+        let old_component = node.__insert_component(component);
+        if !had_component {
+            node.__push_indexed_type_id(TypeId::of::<T>());
+            self.component_index
+                .entry(TypeId::of::<T>())
+                .or_default()
+                .insert(node.id(), handle.clone());
+        }
+        old_component
+    }
+
+    /// Returns an entry for the component of type T belonging to a node, for getting the component or inserting it if it is missing.
+    /// Components defined by the node's class are always present, so they are never replaced.
+    /// Components inserted through the entry are indexed, as with `Universe::insert_component`.
+    pub fn component_entry<T: Send + Sync + 'static>(
+        &mut self,
+        handle: &Handle,
+    ) -> ComponentEntry<'_, T> {
+        self.nodes
+            .get_mut(handle)
+            .expect("No node pointed to by this handle to get a component entry for")
+            .__component_entry(&mut self.component_index)
+    }
+
+    /// Removes the dynamic component of type T from a node and returns it, keeping the component index up to date.
+    /// Components defined by the node's class cannot be removed, and None is returned for them.
+    pub fn remove_component<T: 'static>(&mut self, handle: &Handle) -> Option<T> {
+        let node = self.nodes.get_mut(handle)?;
+        let old_component = node.__remove_component::<T>()?;
+        if node.component::<T>().is_none() {
+            node.__remove_indexed_type_id(TypeId::of::<T>());
+            remove_from_index(&mut self.component_index, TypeId::of::<T>(), node.id());
+        }
+        Some(old_component)
+    }
+
+    /// Creates a copy of the universe, keeping the handles of the original, so handles from the original universe point to the same nodes in the copy.
+    /// Returns `Error::UncloneableClass` if any node's class cannot be cloned (see `Class::clone_box`),
+    /// or `Error::UncloneableComponent` if any node has dynamic components.
+    /// Observers are not copied.
+    pub fn try_clone(&self) -> Result<Universe, Error> {
+        // Cloning the HandleMap copies each slot with its Unique, as well as the map's own Unique, which is what keeps handles valid in the copy.
+        // It clones every stored node, which must not fail part way through, so every node is checked first
+        for node in self.nodes() {
+            node.__try_clone()?;
+        }
+        Ok(Universe {
+            nodes: self.nodes.clone(),
+            roots: self.roots.clone(),
+            next_creation_index: self.next_creation_index,
+            id_index: self.id_index.clone(),
+            class_index: self.class_index.clone(),
+            component_index: self.component_index.clone(),
+            relationships: self.relationships.clone(),
+            observers: Observers::default(),
+        })
+    }
+
+    /// Captures the state of the universe, to be brought back later with `Universe::restore`.
+    /// Returns `Error::UncloneableClass` if any node's class cannot be cloned (see `Class::clone_box`),
+    /// or `Error::UncloneableComponent` if any node has dynamic components, since they could not be brought back.
+    pub fn snapshot(&self) -> Result<UniverseSnapshot, Error> {
+        Ok(UniverseSnapshot {
+            universe: self.try_clone()?,
+        })
+    }
+
+    /// Returns the universe to the state captured by `Universe::snapshot`.
+    /// Handles taken before the snapshot point to the same nodes again, including nodes removed since.
+    /// The observers of this universe are kept, and nodes created after restoring are not given the ids of nodes created since the snapshot.
+    pub fn restore(&mut self, snapshot: UniverseSnapshot) {
+        let observers = std::mem::take(&mut self.observers);
+        let next_creation_index = self.next_creation_index;
+        *self = snapshot.universe;
+        self.observers = observers;
+        self.next_creation_index = self.next_creation_index.max(next_creation_index);
+    }
+
+    /// Creates a copy of a node and all of its descendants, attached to the same parent as the original node.
+    /// Returns the handle of the copy of the node, or None if the handle does not point to a node
+    /// or any class in the subtree cannot be cloned (see `Class::clone_box`).
+    /// Relationships between nodes of the subtree are copied to the copies, but relationships to nodes outside of it are not.
+    /// Dynamic components are not copied.
+    pub fn clone_subtree(&mut self, root_handle: &Handle) -> Option<Handle> {
+        // Clone every class first so that nothing is created if any of them cannot be cloned
+        let root = self.node(root_handle)?;
+        let mut clones = Vec::new();
+        for node in std::iter::once(root).chain(self.descendants(root_handle)) {
+            clones.push((
+                node.handle().clone(),
+                node.parent().cloned(),
+                node.class().clone_box()?,
+                node.name_str().map(str::to_owned),
+            ));
+        }
+
+        // Create the copies, attaching each one to the copy of its parent
+        let mut new_handles: HashMap<Handle, Handle> = HashMap::new();
+        for (handle, parent_handle, class, name) in clones {
+            let new_parent_handle = parent_handle.map(|parent_handle| {
+                new_handles
+                    .get(&parent_handle)
+                    .cloned()
+                    .unwrap_or(parent_handle)
+            });
+            let new_handle = self.__create_node_boxed(new_parent_handle.as_ref(), class);
+            self.nodes.get_mut(&new_handle).unwrap().__set_name(name);
+            new_handles.insert(handle, new_handle);
+        }
+        let relationships = self.relationships_within(&new_handles.keys().collect());
+        self.insert_relationships(relationships, &new_handles);
+        new_handles.remove(root_handle)
+    }
+
+    /// Removes every node from the universe.
+    /// Handles to the removed nodes no longer point to any node.
+    pub fn clear(&mut self) {
+        let handles = self
+            .nodes()
+            .map(|node| node.handle().clone())
+            .collect::<Vec<_>>();
+        for handle in &handles {
+            self.nodes.remove(handle);
+        }
+        self.roots.clear();
+        self.id_index.clear();
+        self.class_index.clear();
+        self.component_index.clear();
+        self.relationships.clear();
+        for handle in &handles {
+            notify(&mut self.observers.removed, handle);
+        }
+    }
+
+    /// Resets the changed state of every component of every node, so that `Node::is_changed` returns false.
+    pub fn clear_change_ticks(&mut self) {
+        for node in self.nodes.values_mut() {
+            node.__clear_changed();
+        }
+    }
+
+    /// Returns an iterator over the ancestors of a node, starting at its parent and ending at its root.
+    pub fn ancestors<'a>(&'a self, handle: &Handle) -> Ancestors<'a> {
+        Ancestors {
+            universe: self,
+            next_handle: self.node(handle).and_then(|node| node.parent().cloned()),
+        }
+    }
+
+    /// Returns how many ancestors a node has, which is 0 for root nodes.
+    /// Returns None if the handle does not point to a node.
+    pub fn depth(&self, handle: &Handle) -> Option<usize> {
+        if !self.contains_node(handle) {
+            return None;
+        }
+        // `change_parent` never creates cycles, so the ancestor chain always ends at a root
+        Some(self.ancestors(handle).count())
+    }
+
+    /// Returns the handle of the root of the tree containing a node, which is the node itself if it is a root node.
+    /// Returns None if the handle does not point to a node.
+    pub fn root_of(&self, handle: &Handle) -> Option<Handle> {
+        let node = self.node(handle)?;
+        // `change_parent` never creates cycles, so the ancestor chain always ends at a root
+        Some(
+            self.ancestors(handle)
+                .last()
+                .unwrap_or(node)
+                .handle()
+                .clone(),
+        )
+    }
+
+    /// Returns the handle of the lowest common ancestor of two nodes, where each node counts as its own ancestor.
+    /// Returns None if the nodes are in different trees or either handle does not point to a node.
+    pub fn lowest_common_ancestor(&self, a: &Handle, b: &Handle) -> Option<Handle> {
+        let mut a_ancestors = HashSet::new();
+        a_ancestors.insert(self.node(a)?.handle().clone());
+        a_ancestors.extend(self.ancestors(a).map(|node| node.handle().clone()));
+        std::iter::once(self.node(b)?)
+            .chain(self.ancestors(b))
+            .map(|node| node.handle())
+            .find(|handle| a_ancestors.contains(*handle))
+            .cloned()
+    }
+
+    /// Returns the handles of the nodes on the path from one node to another through the hierarchy, including both nodes.
+    /// The path climbs from `from` to the lowest common ancestor of the nodes, then descends to `to`.
+    /// Returns None if the nodes are in different trees or either handle does not point to a node.
+    pub fn path_between(&self, from: &Handle, to: &Handle) -> Option<Vec<Handle>> {
+        let lca_handle = self.lowest_common_ancestor(from, to)?;
+        // Each half of the path runs from one of the nodes up to, but not including, the lowest common ancestor
+        let half_path = |handle: &Handle| {
+            std::iter::once(self.node(handle).unwrap())
+                .chain(self.ancestors(handle))
+                .map(|node| node.handle().clone())
+                .take_while(|handle| handle != &lca_handle)
+                .collect::<Vec<_>>()
+        };
+        let mut path = half_path(from);
+        path.push(lca_handle.clone());
+        path.extend(half_path(to).into_iter().rev());
+        Some(path)
+    }
+
+    /// Returns the nearest ancestor of a node which has a component of type T, along with the component.
+    /// The node itself is not considered, only its parent and the parent's ancestors.
+    pub fn nearest_ancestor_with_component<T: 'static>(
+        &self,
+        handle: &Handle,
+    ) -> Option<(&Node, &T)> {
+        self.ancestors(handle)
+            .find_map(|node| Some((node, node.component::<T>()?)))
+    }
+
+    /// Returns the nearest ancestor of a node with the given type of class, along with the class.
+    /// The node itself is not considered, only its parent and the parent's ancestors.
+    pub fn nearest_ancestor_with_class<C: Class>(&self, handle: &Handle) -> Option<(&Node, &C)> {
+        self.ancestors(handle)
+            .find_map(|node| Some((node, node.class_as::<C>()?)))
+    }
+
+    /// Returns an iterator over the descendants of a node in depth-first pre-order, not including the node itself.
+    /// Children are visited in the order they appear in their parent's `children()`.
+    pub fn descendants<'a>(&'a self, root_handle: &Handle) -> Descendants<'a> {
+        let stack = self
+            .node(root_handle)
+            .map(|root| root.children().iter().rev().cloned().collect())
+            .unwrap_or_default();
+        Descendants {
+            universe: self,
+            stack,
+        }
+    }
+
+    /// Returns an iterator over the leaf nodes (nodes with no children) in the universe, in no particular order.
+    pub fn leaves(&self) -> impl Iterator<Item = &Node> + '_ {
+        self.nodes().filter(|node| node.children().is_empty())
+    }
+
+    /// Returns an iterator over the leaf nodes in the subtree rooted at a node, in depth-first pre-order.
+    /// The node itself is included if it has no children.
+    pub fn leaves_of<'a>(&'a self, root_handle: &Handle) -> impl Iterator<Item = &'a Node> + 'a {
+        self.node(root_handle)
+            .into_iter()
+            .chain(self.descendants(root_handle))
+            .filter(|node| node.children().is_empty())
+    }
+
+    /// Returns the number of descendants of a node, not including the node itself.
+    /// Returns 0 if the handle does not point to a node.
+    pub fn descendant_count(&self, handle: &Handle) -> usize {
+        // `change_parent` never creates cycles, so the traversal always ends
+        self.descendants(handle).count()
+    }
+
+    /// Returns an iterator over the descendants of a node in depth-first pre-order, not including the node itself,
+    /// which only descends `max_depth` levels below the node's children. A `max_depth` of 0 yields only the node's children.
+    pub fn descendants_within<'a>(
+        &'a self,
+        root_handle: &Handle,
+        max_depth: usize,
+    ) -> DescendantsWithin<'a> {
+        let stack = self
+            .node(root_handle)
+            .map(|root| {
+                root.children()
+                    .iter()
+                    .rev()
+                    .map(|handle| (handle.clone(), 0))
+                    .collect()
+            })
+            .unwrap_or_default();
+        DescendantsWithin {
+            universe: self,
+            stack,
+            max_depth,
+        }
+    }
+
+    /// Folds the descendants of a node into a single value, visiting them in depth-first pre-order.
+    /// The node itself is not included. Returns `init` if the handle does not point to a node.
+    pub fn fold_descendants<B>(
+        &self,
+        root_handle: &Handle,
+        init: B,
+        f: impl FnMut(B, &Node) -> B,
+    ) -> B {
+        self.descendants(root_handle).fold(init, f)
+    }
+
+    /// Visits a node and its descendants in depth-first pre-order, letting the visitor decide how to continue after each node.
+    /// Children of a node are skipped if the visitor returns `Walk::SkipChildren`, and the walk ends if it returns `Walk::Stop`.
+    /// Does nothing if the handle does not point to a node.
+    pub fn walk(&self, root_handle: &Handle, mut visitor: impl FnMut(&Node) -> Walk) {
+        let mut stack = vec![root_handle];
+        while let Some(handle) = stack.pop() {
+            if let Some(node) = self.node(handle) {
+                match visitor(node) {
+                    Walk::Continue => stack.extend(node.children().iter().rev()),
+                    Walk::SkipChildren => {}
+                    Walk::Stop => return,
+                }
+            }
+        }
+    }
+
+    /// Calls the given function on a node and each of its descendants, in depth-first pre-order.
+    /// Does nothing if the handle does not point to a node.
+    pub fn for_each_in_subtree_mut(&mut self, root_handle: &Handle, mut f: impl FnMut(&mut Node)) {
+        if !self.contains_node(root_handle) {
+            return;
+        }
+
+        // Collect the handles of the subtree first, then visit them
+        for handle in &self.subtree_handles(root_handle) {
+            f(self.nodes.get_mut(handle).unwrap());
+        }
+    }
+
+    // Returns the handles of a node and each of its descendants, in depth-first pre-order
+    fn subtree_handles(&self, root_handle: &Handle) -> Vec<Handle> {
+        let mut handles = vec![root_handle.clone()];
+        handles.extend(
+            self.descendants(root_handle)
+                .map(|node| node.handle().clone()),
+        );
+        handles
+    }
+
+    /// Returns an iterator over the descendants of a node in breadth-first order, not including the node itself.
+    /// Children are visited in the order they appear in their parent's `children()`.
+    pub fn descendants_bfs<'a>(&'a self, root_handle: &Handle) -> DescendantsBfs<'a> {
+        let queue = self
+            .node(root_handle)
+            .map(|root| root.children().iter().cloned().collect())
+            .unwrap_or_default();
+        DescendantsBfs {
+            universe: self,
+            queue,
+        }
+    }
+
+    /// Find a node in the Universe by its unique handle.
+    pub fn node(&self, handle: &Handle) -> Option<&Node> {
+        self.nodes.get(handle).map(|node| &node.0)
+    }
+
+    /// Find a node in the Universe by its handle.
+    pub fn node_mut(&mut self, handle: &Handle) -> Option<&mut Node> {
+        self.nodes.get_mut(handle).map(|node| &mut node.0)
+    }
+
+    /// Find the class of a node in the Universe by its typed handle.
+    pub fn node_typed<C: Class>(&self, handle: &TypedHandle<C>) -> Option<&C> {
+        self.node(handle)?.class_as::<C>()
+    }
+
+    /// Find the class of a node in the Universe by its typed handle.
+    pub fn node_typed_mut<C: Class>(&mut self, handle: &TypedHandle<C>) -> Option<&mut C> {
+        self.node_mut(handle)?.class_as_mut::<C>()
+    }
+
+    /// Find the handle of a node in the Universe by its id (see `Node::id`).
+    /// Returns None if no node in the universe has the id.
+    pub fn handle_from_id(&self, id: u64) -> Option<Handle> {
+        self.id_index.get(&id).cloned()
+    }
+
+    /// Find two different nodes in the Universe by their handles, and borrow both of them mutably.
+    /// Returns None if the handles are equal or either of them does not point to a node.
+    /// This scans every node in the universe (see `nodes_disjoint_mut`).
+    pub fn node_pair_mut(&mut self, a: &Handle, b: &Handle) -> Option<(&mut Node, &mut Node)> {
+        if a == b {
+            return None;
+        }
+        let mut nodes = self.nodes_disjoint_mut(&[a.clone(), b.clone()]).into_iter();
+        Some((nodes.next().flatten()?, nodes.next().flatten()?))
+    }
+
+    /// Find any number of different nodes in the Universe by their handles, and borrow all of them mutably.
+    /// The returned nodes are in the same order as the handles.
+    /// Panics if the same handle is given more than once.
+    /// This scans every node in the universe once, so it takes time proportional to the number of nodes plus the number of handles.
+    pub fn nodes_disjoint_mut(&mut self, handles: &[Handle]) -> Vec<Option<&mut Node>> {
+        let mut indices = HashMap::with_capacity(handles.len());
+        for (index, handle) in handles.iter().enumerate() {
+            assert!(
+                indices.insert(handle, index).is_none(),
+                "The same handle was given more than once"
+            );
+        }
+        let mut nodes: Vec<Option<&mut Node>> = handles.iter().map(|_| None).collect();
+        for node in self.nodes.values_mut() {
+            if let Some(index) = indices.get(node.handle()) {
+                nodes[*index] = Some(node);
+            }
+        }
+        nodes
+    }
+
+    /// Returns the parent of a node.
+    /// Returns None if the node is a root node or the handle does not point to a node.
+    pub fn parent_node(&self, handle: &Handle) -> Option<&Node> {
+        self.node(self.node(handle)?.parent()?)
+    }
+
+    /// Returns the parent of a node, mutably.
+    /// Returns None if the node is a root node or the handle does not point to a node.
+    pub fn parent_node_mut(&mut self, handle: &Handle) -> Option<&mut Node> {
+        let parent_handle = self.node(handle)?.parent()?.clone();
+        self.node_mut(&parent_handle)
+    }
+
+    /// Returns an iterator over the children of a node, in the order they appear in its `children()`.
+    /// Yields nothing if the handle does not point to a node.
+    pub fn children_nodes<'a>(&'a self, handle: &Handle) -> impl Iterator<Item = &'a Node> {
+        self.node(handle)
+            .map(|node| node.children())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(move |child_handle| self.node(child_handle))
+    }
+
+    /// Returns the first child of a node, if it has any children.
+    pub fn first_child(&self, handle: &Handle) -> Option<&Node> {
+        self.nth_child(handle, 0)
+    }
+
+    /// Returns the child of a node at the given position among its children, if there is one.
+    pub fn nth_child(&self, handle: &Handle, n: usize) -> Option<&Node> {
+        self.node(self.node(handle)?.children().get(n)?)
+    }
+
+    /// Returns the sibling after a node among its parent's children, or among the root nodes for a root node.
+    pub fn next_sibling(&self, handle: &Handle) -> Option<&Node> {
+        let (siblings, index) = self.sibling_position(handle)?;
+        self.node(siblings.get(index + 1)?)
+    }
+
+    /// Returns the sibling before a node among its parent's children, or among the root nodes for a root node.
+    pub fn prev_sibling(&self, handle: &Handle) -> Option<&Node> {
+        let (siblings, index) = self.sibling_position(handle)?;
+        self.node(siblings.get(index.checked_sub(1)?)?)
+    }
+
+    // Returns the handles of a node and its siblings, along with the position of the node among them
+    fn sibling_position(&self, handle: &Handle) -> Option<(&[Handle], usize)> {
+        let siblings = match self.node(handle)?.parent() {
+            Some(parent_handle) => self.node(parent_handle)?.children(),
+            None => self.root_node_handles(),
+        };
+        let index = siblings.iter().position(|sibling| sibling == handle)?;
+        Some((siblings, index))
+    }
+
+    /// Returns an iterator over the children of a node, borrowing all of them mutably.
+    /// The children are in the order they appear in the node's `children()`.
+    /// Yields nothing if the handle does not point to a node.
+    /// This scans every node in the universe (see `nodes_disjoint_mut`).
+    pub fn children_nodes_mut(&mut self, handle: &Handle) -> impl Iterator<Item = &mut Node> {
+        let child_handles = self
+            .node(handle)
+            .map(|node| node.children().to_vec())
+            .unwrap_or_default();
+        self.nodes_disjoint_mut(&child_handles)
+            .into_iter()
+            .flatten()
+    }
+
+    /// Find a node in the Universe by its name.
+    /// If more than one node has the name, any one of them may be returned.
+    pub fn node_by_name(&self, name: &str) -> Option<&Node> {
+        self.nodes().find(|node| node.name_str() == Some(name))
+    }
+
+    /// Returns an iterator over the nodes in the Universe with the given name.
+    pub fn nodes_by_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.nodes()
+            .filter(move |node| node.name_str() == Some(name))
+    }
+
+    /// Returns an iterator over the nodes with the given handles.
+    pub fn nodes_with_handles<'a>(
+        &'a self,
+        handles: &'a [Handle],
+    ) -> impl Iterator<Item = Option<&'a Node>> {
+        handles.iter().map(|handle| self.node(handle))
+    }
+
+    /// Calls the given function on the nodes with the given handles.
+    pub fn using_nodes_with_handles<'a, R, F: FnMut(Option<&'a Node>) -> R + 'a>(
+        &'a self,
+        handles: &'a [Handle],
+        mut f: F,
+    ) -> impl Iterator<Item = R> + 'a {
+        handles.iter().map(move |handle| f(self.node(handle)))
+    }
+
+    /// Calls the given function on the nodes with the given handles.
+    pub fn using_nodes_with_handles_mut<R, F: FnMut(Option<&mut Node>) -> R>(
+        &mut self,
+        handles: &[Handle],
+        mut f: F,
+    ) -> Vec<R> {
+        let mut results = Vec::new();
+        for handle in handles {
+            results.push(f(self.node_mut(handle)));
+        }
+        results
+    }
+
+    /// Returns a slice containing the handles of the root nodes in the universe (nodes with no parent).
+    pub fn root_node_handles(&self) -> &[Handle] {
+        &self.roots
+    }
+
+    /// Calls the given function on the root nodes in the universe.
+    pub fn using_root_nodes<'a, R: 'a, F: FnMut(Option<&'a Node>) -> R + 'a>(
+        &'a self,
+        f: F,
+    ) -> impl Iterator<Item = R> + 'a {
+        self.using_nodes_with_handles(self.root_node_handles(), f)
+    }
+
+    /// Calls the given function on the root nodes in the universe.
+    pub fn using_root_nodes_mut<R, F: FnMut(Option<&mut Node>) -> R>(&mut self, f: F) -> Vec<R> {
+        let handles = self.root_node_handles().to_owned();
+        self.using_nodes_with_handles_mut(&handles, f)
+    }
+
+    /// Returns whether the universe contains a node with the given handle.
+    pub fn contains_node(&self, handle: &Handle) -> bool {
+        self.nodes.contains(handle)
+    }
+
+    /// Returns the number of nodes in the universe.
+    pub fn node_count(&self) -> usize {
+        // The id index holds exactly one entry per node, so the nodes themselves are not visited
+        self.id_index.len()
+    }
+
+    /// Returns whether the universe contains no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.id_index.is_empty()
+    }
+
+    /// Returns a Graphviz DOT digraph of the node hierarchy, with edges running from parent to child.
+    /// Each node is labeled with its class's name and its handle.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(|node| format!("{} {:?}", node.class().name(), node.handle()))
+    }
+
+    /// Returns a Graphviz DOT digraph of the node hierarchy, with edges running from parent to child.
+    /// Each node is labeled with the result of calling `label` on it.
+    pub fn to_dot_with(&self, mut label: impl FnMut(&Node) -> String) -> String {
+        let ids = self
+            .nodes()
+            .enumerate()
+            .map(|(id, node)| (node.handle().clone(), id))
+            .collect::<HashMap<_, _>>();
+        let mut dot = String::from("digraph {\n");
+        for node in self.nodes() {
+            let label = label(node).replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(dot, "    n{} [label=\"{}\"];", ids[node.handle()], label).unwrap();
+        }
+        for node in self.nodes() {
+            for child_handle in node.children() {
+                writeln!(
+                    dot,
+                    "    n{} -> n{};",
+                    ids[node.handle()],
+                    ids[child_handle]
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Returns an iterator over the nodes with the given type of class, in the order they were inserted into the universe.
+    /// Unlike `nodes().with_class::<C>()`, this is served from an index rather than scanning every node.
+    pub fn nodes_of_class<C: Class>(&self) -> impl Iterator<Item = &Node> {
+        self.class_index
+            .get(&TypeId::of::<C>())
+            .into_iter()
+            .flat_map(|handles| handles.values())
+            .map(move |handle| self.node(handle).unwrap())
+    }
+
+    /// Returns an iterator over the nodes with a component of the given type id, in no particular order.
+    /// This is the type-erased equivalent of `nodes().with_component::<C>()`, for when the component type is only known at runtime.
+    pub fn nodes_with_component_id(&self, type_id: TypeId) -> impl Iterator<Item = &Node> + '_ {
+        self.nodes()
+            .filter(move |node| node.has_component_id(type_id))
+    }
+
+    /// Returns the handle of every node with a component of type T, along with a clone of the component.
+    /// The pairs are in the order the nodes were inserted into the universe, and can be processed without borrowing the universe.
+    pub fn collect_component<T: Clone + 'static>(&self) -> Vec<(Handle, T)> {
+        self.nodes_sorted()
+            .with_component::<T>()
+            .map(|(node, component)| (node.handle().clone(), component.clone()))
+            .collect()
+    }
+
+    /// Returns the nodes in the universe grouped by the name of their class.
+    /// Each group is in the order the nodes were inserted into the universe.
+    pub fn group_by_class(&self) -> HashMap<&'static str, Vec<&Node>> {
+        let mut groups = HashMap::<_, Vec<_>>::new();
+        for node in self.nodes_sorted() {
+            groups.entry(node.class().name()).or_default().push(node);
+        }
+        groups
+    }
+
+    /// Returns the handles of the nodes in the universe grouped by the name of their class.
+    /// Unlike `group_by_class`, this does not borrow the universe, so the nodes can be modified while using it.
+    pub fn group_by_class_handles(&self) -> HashMap<&'static str, Vec<Handle>> {
+        self.group_by_class()
+            .into_iter()
+            .map(|(name, nodes)| {
+                let handles = nodes
+                    .into_iter()
+                    .map(|node| node.handle().clone())
+                    .collect();
+                (name, handles)
+            })
+            .collect()
+    }
+
+    /// Returns an iterator over the nodes exposing a component of type C, along with the component,
+    /// in the order the nodes were inserted into the universe.
+    /// Unlike `nodes().with_component::<C>()`, this is served from an index rather than scanning every node.
+    /// Class components are only indexed if the class implements `Class::components_iter`,
+    /// and dynamic components are indexed when they are inserted through `Universe::insert_component`.
+    pub fn nodes_with_component_indexed<C: 'static>(&self) -> impl Iterator<Item = (&Node, &C)> {
+        self.component_index
+            .get(&TypeId::of::<C>())
+            .into_iter()
+            .flat_map(|handles| handles.values())
+            .filter_map(move |handle| {
+                let node = self.node(handle)?;
+                Some((node, node.component::<C>()?))
+            })
+    }
+
+    /// Returns the number of nodes in the universe for which the predicate returns true.
+    pub fn count_matching(&self, predicate: impl Fn(&Node) -> bool) -> usize {
+        self.nodes().filter(|node| predicate(node)).count()
+    }
+
+    /// Returns whether the predicate returns true for any node in the universe.
+    pub fn any_matching(&self, predicate: impl Fn(&Node) -> bool) -> bool {
+        self.nodes().any(predicate)
+    }
+
+    /// Returns whether any node in the universe has a component of type T, either from its class or as a dynamic component.
+    /// Stops at the first node found.
+    pub fn any_node_with_component<T: 'static>(&self) -> bool {
+        self.nodes().any(|node| node.has_component::<T>())
+    }
+
+    /// Returns whether any node in the universe has the given type of class.
+    /// This is answered from the class index, without visiting any nodes.
+    pub fn any_node_with_class<C: Class>(&self) -> bool {
+        self.class_index.contains_key(&TypeId::of::<C>())
+    }
+
+    /// Returns whether the predicate returns true for every node in the universe.
+    /// Returns true if the universe is empty.
+    pub fn all_matching(&self, predicate: impl Fn(&Node) -> bool) -> bool {
+        self.nodes().all(predicate)
+    }
+
+    /// Returns an iterator over the handles of all the nodes in the universe.
+    pub fn node_handles(&self) -> impl Iterator<Item = Handle> + '_ {
+        self.nodes.handles()
+    }
+
+    /// Returns the handles of all the nodes in the universe, collected so that they can be used without borrowing the universe.
+    /// The handles can be split into chunks and processed separately, looking each node up again with `node`.
+    pub fn snapshot_handles(&self) -> Vec<Handle> {
+        self.node_handles().collect()
+    }
+
+    /// Returns an iterator over all the nodes in the universe, in the order they were inserted into it.
+    /// Unlike `nodes()`, this order is stable regardless of which nodes have been removed.
+    pub fn nodes_sorted(&self) -> impl Iterator<Item = &Node> {
+        self.id_index
+            .values()
+            .map(move |handle| self.node(handle).unwrap())
+    }
+
+    /// Returns an iterator over all the nodes in the universe, in no particular order.
+    /// See `nodes_sorted` for deterministic iteration.
+    pub fn nodes(&self) -> NodeValues<'_> {
+        NodeValues {
+            iter: self.nodes.values(),
+        }
+    }
+
+    /// Returns an iterator over all the nodes in the universe, in no particular order.
+    pub fn nodes_mut(&mut self) -> NodeValuesMut<'_> {
+        NodeValuesMut {
+            iter: self.nodes.values_mut(),
+        }
+    }
+
+    /// Returns a parallel iterator over all the nodes in the universe.
+    #[cfg(feature = "rayon")]
+    pub fn par_nodes(&self) -> impl ParallelIterator<Item = &Node> {
+        self.nodes().collect::<Vec<_>>().into_par_iter()
+    }
+
+    /// Calls the given function on every node in the universe in parallel, in no particular order.
+    #[cfg(feature = "rayon")]
+    pub fn par_for_each(&self, f: impl Fn(&Node) + Sync) {
+        self.par_nodes().for_each(&f);
+    }
+
+    /// Returns a parallel iterator over all the nodes in the universe.
+    /// Each node is visited by exactly one thread.
+    #[cfg(feature = "rayon")]
+    pub fn par_nodes_mut(&mut self) -> impl ParallelIterator<Item = &mut Node> {
+        self.nodes_mut().collect::<Vec<_>>().into_par_iter()
+    }
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> IntoIterator for &'a Universe {
+    type Item = &'a Node;
+    type IntoIter = NodeValues<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Universe {
+    type Item = &'a mut Node;
+    type IntoIter = NodeValuesMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes_mut()
+    }
+}
+
+impl Debug for Universe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Universe")
+            .field("nodes", &self.nodes)
+            .field("roots", &self.roots)
+            .field("class_index", &self.class_index)
+            .field("component_index", &self.component_index)
+            .field("relationships", &self.relationships)
+            .finish_non_exhaustive()
+    }
+}
+
+// The edges of one type of relationship, indexed in both directions so that a node's edges are found without scanning the others
+#[derive(Debug, Clone, Default)]
+struct Relationship {
+    // The nodes each node is related to, in the order they were related
+    to: HashMap<Handle, Vec<Handle>>,
+    // The nodes each node is related from
+    from: HashMap<Handle, Vec<Handle>>,
+}
+
+impl Relationship {
+    fn insert(&mut self, from: &Handle, to: &Handle) {
+        let related = self.to.entry(from.clone()).or_default();
+        if !related.contains(to) {
+            related.push(to.clone());
+            self.from.entry(to.clone()).or_default().push(from.clone());
+        }
     }
 
-    /// Changes a node's parent.
-    /// Returns the node's old parent's unique Handle, if it had one.
-    pub fn change_parent(
-        &mut self,
-        node_handle: &Handle,
-        new_parent_handle: Option<&Handle>,
-    ) -> Option<Handle> {
-        let old_parent_handle = self
-            .node(node_handle)
-            .expect("No node pointed to by this handle to change the parent of")
-            .parent()
-            .cloned();
-        if let Some(old_parent_handle) = &old_parent_handle {
-            self.nodes
-                .get_mut(old_parent_handle)
-                .unwrap()
-                .__remove_child_handle(node_handle);
+    fn remove(&mut self, from: &Handle, to: &Handle) -> Option<()> {
+        remove_edge(&mut self.to, from, to)?;
+        remove_edge(&mut self.from, to, from);
+        Some(())
+    }
+
+    fn remove_node(&mut self, handle: &Handle) {
+        for to in self.to.remove(handle).unwrap_or_default() {
+            remove_edge(&mut self.from, &to, handle);
         }
-        if let Some(new_parent_handle) = new_parent_handle {
-            self.nodes
-                .get_mut(new_parent_handle)
-                .unwrap()
-                .__push_child_handle(node_handle.clone());
+        for from in self.from.remove(handle).unwrap_or_default() {
+            remove_edge(&mut self.to, &from, handle);
         }
-        self.node_mut(node_handle)
-            .expect("No node pointed to by this handle to change the parent of")
-            .__set_parent_handle(new_parent_handle);
-        old_parent_handle
     }
+}
 
-    /// Find a node in the Universe by its unique handle.
-    pub fn node(&self, handle: &Handle) -> Option<&Node> {
-        self.nodes.get(handle)
+// Removes one edge from a direction of a relationship, dropping the node's entry once it is empty
+fn remove_edge(
+    edges: &mut HashMap<Handle, Vec<Handle>>,
+    node: &Handle,
+    other: &Handle,
+) -> Option<()> {
+    let related = edges.get_mut(node)?;
+    let index = related.iter().position(|handle| handle == other)?;
+    related.remove(index);
+    if related.is_empty() {
+        edges.remove(node);
     }
+    Some(())
+}
 
-    /// Find a node in the Universe by its handle.
-    pub fn node_mut(&mut self, handle: &Handle) -> Option<&mut Node> {
-        self.nodes.get_mut(handle)
+// The handles of the nodes of each type of class or component, keyed by its type id and then by node id.
+// Keying the handles by node id keeps them in creation order, and lets a node be removed without scanning the others.
+pub(crate) type TypeIndex = HashMap<TypeId, BTreeMap<u64, Handle>>;
+
+// Removes a node from the entry of an index, dropping the entry once it is empty
+fn remove_from_index(index: &mut TypeIndex, type_id: TypeId, id: u64) {
+    if let Some(handles) = index.get_mut(&type_id) {
+        handles.remove(&id);
+        if handles.is_empty() {
+            index.remove(&type_id);
+        }
     }
+}
 
-    /// Returns an iterator over the nodes with the given handles.
-    pub fn nodes_with_handles<'a>(
-        &'a self,
-        handles: &'a [Handle],
-    ) -> impl Iterator<Item = Option<&'a Node>> {
-        handles.iter().map(|handle| self.nodes.get(handle))
+/// Returned by the visitor of `Universe::walk` to decide how the walk continues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Walk {
+    /// Continue the walk, descending into the children of the visited node.
+    Continue,
+    /// Continue the walk, but skip the children of the visited node.
+    SkipChildren,
+    /// End the walk.
+    Stop,
+}
+
+/// The structural changes between two universes, returned by `Universe::diff`.
+/// Each list of handles is in the order the nodes were inserted into their universe.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UniverseDiff {
+    /// Nodes which are only in the other universe.
+    pub added: Vec<Handle>,
+    /// Nodes which are only in this universe.
+    pub removed: Vec<Handle>,
+    /// Nodes which are in both universes, but with different parents.
+    pub reparented: Vec<Handle>,
+}
+
+// A node as stored in the universe.
+// Nodes do not implement Clone, since cloning their class can fail, so this only clones nodes already checked by `Universe::try_clone`.
+struct StoredNode(Node);
+
+impl Clone for StoredNode {
+    fn clone(&self) -> Self {
+        StoredNode(
+            self.0
+                .__try_clone()
+                .expect("Node was not checked before the universe was cloned"),
+        )
     }
+}
 
-    /// Calls the given function on the nodes with the given handles.
-    pub fn using_nodes_with_handles<'a, R, F: FnMut(Option<&'a Node>) -> R + 'a>(
-        &'a self,
-        handles: &'a [Handle],
-        mut f: F,
-    ) -> impl Iterator<Item = R> + 'a {
-        handles.iter().map(move |handle| f(self.nodes.get(handle)))
+impl Debug for StoredNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
     }
+}
 
-    /// Calls the given function on the nodes with the given handles.
-    pub fn using_nodes_with_handles_mut<R, F: FnMut(Option<&mut Node>) -> R>(
-        &mut self,
-        handles: &[Handle],
-        mut f: F,
-    ) -> Vec<R> {
-        let mut results = Vec::new();
-        for handle in handles {
-            results.push(f(self.nodes.get_mut(handle)));
-        }
-        results
+impl Deref for StoredNode {
+    type Target = Node;
+
+    fn deref(&self) -> &Node {
+        &self.0
     }
+}
 
-    /// Returns a slice containing the handles of the root nodes in the universe (nodes with no parent).
-    pub fn root_node_handles(&self) -> &[Handle] {
-        &self.roots
+impl DerefMut for StoredNode {
+    fn deref_mut(&mut self) -> &mut Node {
+        &mut self.0
     }
+}
 
-    /// Calls the given function on the root nodes in the universe.
-    pub fn using_root_nodes<'a, R: 'a, F: FnMut(Option<&'a Node>) -> R + 'a>(
-        &'a self,
-        f: F,
-    ) -> impl Iterator<Item = R> + 'a {
-        self.using_nodes_with_handles(self.root_node_handles(), f)
+/// An iterator over all the nodes in a universe, in no particular order. Returned by `Universe::nodes`.
+pub struct NodeValues<'a> {
+    iter: HandleMapValues<'a, StoredNode>,
+}
+
+impl<'a> Iterator for NodeValues<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|node| &node.0)
     }
+}
 
-    /// Calls the given function on the root nodes in the universe.
-    pub fn using_root_nodes_mut<R, F: FnMut(Option<&mut Node>) -> R>(&mut self, f: F) -> Vec<R> {
-        let handles = self.root_node_handles().to_owned();
-        self.using_nodes_with_handles_mut(&handles, f)
+/// An iterator over all the nodes in a universe, borrowing them mutably, in no particular order. Returned by `Universe::nodes_mut`.
+pub struct NodeValuesMut<'a> {
+    iter: HandleMapValuesMut<'a, StoredNode>,
+}
+
+impl<'a> Iterator for NodeValuesMut<'a> {
+    type Item = &'a mut Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|node| &mut node.0)
     }
+}
 
-    /// Returns whether the universe contains a node with the given handle.
-    pub fn contains_node(&self, handle: &Handle) -> bool {
-        self.nodes.contains(handle)
+/// The captured state of a universe, returned by `Universe::snapshot` and passed to `Universe::restore`.
+#[derive(Debug)]
+pub struct UniverseSnapshot {
+    universe: Universe,
+}
+
+/// Builds a node one piece at a time before creating it in a universe. Returned by `Universe::build_node`.
+pub struct NodeBuilder<'a> {
+    universe: &'a mut Universe,
+    parent_handle: Option<Handle>,
+    node: Node,
+}
+
+impl NodeBuilder<'_> {
+    /// Sets the class of the node, replacing any class given before.
+    pub fn with_class<C: Class + 'static>(mut self, class: C) -> Self {
+        self.node.__set_class(Box::new(class));
+        self
     }
 
-    /// Returns an iterator over all the nodes in the universe.
-    pub fn nodes(&self) -> HandleMapValues<Node> {
-        self.nodes.values()
+    /// Attaches a dynamic component of type T to the node, replacing any dynamic component of type T given before.
+    pub fn with_component<T: Send + Sync + 'static>(mut self, component: T) -> Self {
+        self.node.__insert_component(component);
+        self
     }
 
-    /// Returns an iterator over all the nodes in the universe.
-    pub fn nodes_mut(&mut self) -> HandleMapValuesMut<Node> {
-        self.nodes.values_mut()
+    /// Creates the node in the universe. Returns the node's unique Handle.
+    pub fn spawn(self) -> Handle {
+        if let Some(parent_handle) = &self.parent_handle {
+            assert!(
+                self.universe.contains_node(parent_handle),
+                "No node pointed to by this handle to create the node under"
+            );
+        }
+        self.universe
+            .__insert_node(self.parent_handle.as_ref(), self.node)
     }
 }
 
@@ -153,9 +1660,23 @@ pub trait NodesIter<'a>: Sized + Iterator<Item = &'a Node> {
     /// Filter the iterator to only include nodes with the given class.
     fn with_class<C: Class>(self) -> NodesWithClass<'a, Self, C>;
     /// Filter the iterator to only include nodes with the given component.
+    /// Both class components and dynamic components are considered, as with `Node::component`.
     fn with_component<C>(self) -> NodesWithComponent<'a, Self, C>;
+    /// Filter the iterator to only include nodes without the given component.
+    fn without_component<C: 'static>(self) -> NodesWithoutComponent<'a, Self, C>;
+    /// Filter the iterator to only include nodes with all of the given components.
+    /// `T` is a tuple of component types, such as `(A, B)`.
+    fn with_components<T: ComponentTuple<'a>>(self) -> NodesWithComponents<'a, Self, T>;
+    /// Filter the iterator to only include nodes with the given class and component,
+    /// yielding the class along with the component.
+    fn with_class_component<C: Class, T: 'static>(self) -> NodesWithClassComponent<'a, Self, C, T>;
+    /// Filter the iterator to only include nodes whose component of the given type has changed
+    /// since the last call to `Universe::clear_change_ticks`.
+    fn changed<C: 'static>(self) -> NodesChanged<'a, Self, C>;
     /// Retrieve the handles of the nodes this iterator yields.
     fn handles(self) -> NodesToHandles<'a, Self>;
+    /// Collect owned copies of the handles of the nodes this iterator yields.
+    fn collect_handles(self) -> Vec<Handle>;
 }
 
 impl<'a, I: Iterator<Item = &'a Node>> NodesIter<'a> for I {
@@ -173,11 +1694,68 @@ impl<'a, I: Iterator<Item = &'a Node>> NodesIter<'a> for I {
         }
     }
 
+    fn without_component<C: 'static>(self) -> NodesWithoutComponent<'a, Self, C> {
+        NodesWithoutComponent {
+            iter: self,
+            __marker: PhantomData,
+        }
+    }
+
+    fn with_components<T: ComponentTuple<'a>>(self) -> NodesWithComponents<'a, Self, T> {
+        NodesWithComponents {
+            iter: self,
+            __marker: PhantomData,
+        }
+    }
+
+    fn with_class_component<C: Class, T: 'static>(self) -> NodesWithClassComponent<'a, Self, C, T> {
+        NodesWithClassComponent {
+            iter: self,
+            __marker: PhantomData,
+        }
+    }
+
+    fn changed<C: 'static>(self) -> NodesChanged<'a, Self, C> {
+        NodesChanged {
+            iter: self,
+            __marker: PhantomData,
+        }
+    }
+
     fn handles(self) -> NodesToHandles<'a, Self> {
         NodesToHandles { iter: self }
     }
+
+    fn collect_handles(self) -> Vec<Handle> {
+        self.map(|node| node.handle().clone()).collect()
+    }
+}
+
+/// A tuple of component types which can be queried on a node all at once.
+pub trait ComponentTuple<'a> {
+    /// The node along with a reference to each of the components.
+    type Item;
+
+    /// Returns the node along with each of the components, if the node has all of them.
+    fn query(node: &'a Node) -> Option<Self::Item>;
+}
+
+macro_rules! impl_component_tuple {
+    ($($type:ident),*) => {
+        impl<'a, $($type: 'static),*> ComponentTuple<'a> for ($($type,)*) {
+            type Item = (&'a Node, $(&'a $type),*);
+
+            fn query(node: &'a Node) -> Option<Self::Item> {
+                Some((node, $(node.component::<$type>()?),*))
+            }
+        }
+    };
 }
 
+impl_component_tuple!(A, B);
+impl_component_tuple!(A, B, C);
+impl_component_tuple!(A, B, C, D);
+
 /// An iterator over nodes in a universe, filtered to a specific class.
 pub struct NodesWithClass<'a, I: Iterator<Item = &'a Node>, C: Class + 'a> {
     iter: I,
@@ -216,6 +1794,88 @@ impl<'a, I: Iterator<Item = &'a Node>, C: 'static> Iterator for NodesWithCompone
     }
 }
 
+/// An iterator over nodes in a universe, filtered to nodes without a specific component.
+pub struct NodesWithoutComponent<'a, I: Iterator<Item = &'a Node>, C: 'static> {
+    iter: I,
+    __marker: std::marker::PhantomData<C>,
+}
+
+impl<'a, I: Iterator<Item = &'a Node>, C: 'static> Iterator for NodesWithoutComponent<'a, I, C> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.iter.next() {
+            if node.component::<C>().is_none() {
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over nodes in a universe, filtered to a specific class and component.
+pub struct NodesWithClassComponent<'a, I: Iterator<Item = &'a Node>, C: Class + 'a, T: 'static> {
+    iter: I,
+    __marker: std::marker::PhantomData<(C, T)>,
+}
+
+impl<'a, I: Iterator<Item = &'a Node>, C: Class + 'a, T: 'static> Iterator
+    for NodesWithClassComponent<'a, I, C, T>
+{
+    type Item = (&'a C, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.iter.next() {
+            if let (Some(class), Some(component)) = (node.class_as::<C>(), node.component::<T>()) {
+                return Some((class, component));
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over nodes in a universe, filtered to nodes whose component of a specific type has changed.
+pub struct NodesChanged<'a, I: Iterator<Item = &'a Node>, C: 'static> {
+    iter: I,
+    __marker: std::marker::PhantomData<C>,
+}
+
+impl<'a, I: Iterator<Item = &'a Node>, C: 'static> Iterator for NodesChanged<'a, I, C> {
+    type Item = (&'a Node, &'a C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.iter.next() {
+            if node.is_changed::<C>() {
+                if let Some(component) = node.component::<C>() {
+                    return Some((node, component));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over nodes in a universe, filtered to a specific set of components.
+pub struct NodesWithComponents<'a, I: Iterator<Item = &'a Node>, T: ComponentTuple<'a>> {
+    iter: I,
+    __marker: std::marker::PhantomData<T>,
+}
+
+impl<'a, I: Iterator<Item = &'a Node>, T: ComponentTuple<'a>> Iterator
+    for NodesWithComponents<'a, I, T>
+{
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.iter.next() {
+            if let Some(item) = T::query(node) {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
 /// An iterator over handles of nodes in a universe.
 pub struct NodesToHandles<'a, I: Iterator<Item = &'a Node>> {
     iter: I,
@@ -233,9 +1893,14 @@ pub trait NodesIterMut<'a>: Sized + Iterator<Item = &'a mut Node> {
     /// Filter the iterator to only include nodes with the given class.
     fn with_class<C: Class>(self) -> NodesWithClassMut<'a, Self, C>;
     /// Filter the iterator to only include nodes with the given component.
+    /// Both class components and dynamic components are considered, as with `Node::component`.
     fn with_component<C>(self) -> NodesWithComponentMut<'a, Self, C>;
+    /// Filter the iterator to only include nodes without the given component.
+    fn without_component<C: 'static>(self) -> NodesWithoutComponentMut<'a, Self, C>;
     /// Retrieve the handles of the nodes this iterator yields.
     fn handles(self) -> NodesToHandlesMut<'a, Self>;
+    /// Collect owned copies of the handles of the nodes this iterator yields.
+    fn collect_handles(self) -> Vec<Handle>;
 }
 
 impl<'a, I: Iterator<Item = &'a mut Node>> NodesIterMut<'a> for I {
@@ -253,9 +1918,20 @@ impl<'a, I: Iterator<Item = &'a mut Node>> NodesIterMut<'a> for I {
         }
     }
 
+    fn without_component<C: 'static>(self) -> NodesWithoutComponentMut<'a, Self, C> {
+        NodesWithoutComponentMut {
+            iter: self,
+            __marker: PhantomData,
+        }
+    }
+
     fn handles(self) -> NodesToHandlesMut<'a, Self> {
         NodesToHandlesMut { iter: self }
     }
+
+    fn collect_handles(self) -> Vec<Handle> {
+        self.map(|node| node.handle().clone()).collect()
+    }
 }
 
 /// An iterator over nodes in a universe, filtered to a specific class.
@@ -298,6 +1974,27 @@ impl<'a, I: Iterator<Item = &'a mut Node>, C: 'static> Iterator
     }
 }
 
+/// An iterator over nodes in a universe, filtered to nodes without a specific component.
+pub struct NodesWithoutComponentMut<'a, I: Iterator<Item = &'a mut Node>, C: 'static> {
+    iter: I,
+    __marker: std::marker::PhantomData<C>,
+}
+
+impl<'a, I: Iterator<Item = &'a mut Node>, C: 'static> Iterator
+    for NodesWithoutComponentMut<'a, I, C>
+{
+    type Item = &'a mut Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.iter.next() {
+            if node.component::<C>().is_none() {
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
 /// An iterator over handles of nodes in a universe.
 pub struct NodesToHandlesMut<'a, I: Iterator<Item = &'a mut Node>> {
     iter: I,
@@ -310,3 +2007,87 @@ impl<'a, I: Iterator<Item = &'a mut Node>> Iterator for NodesToHandlesMut<'a, I>
         self.iter.next().map(|node| node.handle())
     }
 }
+
+/// An iterator over the ancestors of a node in a universe.
+pub struct Ancestors<'a> {
+    universe: &'a Universe,
+    next_handle: Option<Handle>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.universe.node(self.next_handle.as_ref()?)?;
+        self.next_handle = node.parent().cloned();
+        Some(node)
+    }
+}
+
+/// An iterator over the descendants of a node in a universe, in depth-first pre-order.
+pub struct Descendants<'a> {
+    universe: &'a Universe,
+    stack: Vec<Handle>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(handle) = self.stack.pop() {
+            if let Some(node) = self.universe.node(&handle) {
+                self.stack.extend(node.children().iter().rev().cloned());
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the descendants of a node in a universe up to a maximum depth, in depth-first pre-order.
+pub struct DescendantsWithin<'a> {
+    universe: &'a Universe,
+    stack: Vec<(Handle, usize)>,
+    max_depth: usize,
+}
+
+impl<'a> Iterator for DescendantsWithin<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((handle, depth)) = self.stack.pop() {
+            if let Some(node) = self.universe.node(&handle) {
+                if depth < self.max_depth {
+                    self.stack.extend(
+                        node.children()
+                            .iter()
+                            .rev()
+                            .map(|handle| (handle.clone(), depth + 1)),
+                    );
+                }
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the descendants of a node in a universe, in breadth-first order.
+pub struct DescendantsBfs<'a> {
+    universe: &'a Universe,
+    queue: VecDeque<Handle>,
+}
+
+impl<'a> Iterator for DescendantsBfs<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(handle) = self.queue.pop_front() {
+            if let Some(node) = self.universe.node(&handle) {
+                self.queue.extend(node.children().iter().cloned());
+                return Some(node);
+            }
+        }
+        None
+    }
+}